@@ -0,0 +1,252 @@
+use crate::TempoSection;
+use serde_json::{Value, json};
+use std::path::Path;
+use std::fs::File;
+use std::io::{Read, Write};
+use std::fmt;
+use std::cmp::Ordering::Equal;
+
+#[derive(Debug)]
+pub enum AnnotationError {
+	Io(std::io::Error),
+	Json(serde_json::Error),
+	Format(String),
+}
+
+impl fmt::Display for AnnotationError {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match self {
+			AnnotationError::Io(e) => write!(f, "IO error: {}", e),
+			AnnotationError::Json(e) => write!(f, "JSON error: {}", e),
+			AnnotationError::Format(e) => write!(f, "Invalid annotation: {}", e),
+		}
+	}
+}
+
+impl std::error::Error for AnnotationError {}
+
+impl From<std::io::Error> for AnnotationError {
+	fn from(e: std::io::Error) -> Self { AnnotationError::Io(e) }
+}
+
+impl From<serde_json::Error> for AnnotationError {
+	fn from(e: serde_json::Error) -> Self { AnnotationError::Json(e) }
+}
+
+/// Tempo sections and beat times of a single piece.
+#[derive(Debug, Clone, Default)]
+pub struct TempoAnnotation {
+	sections: Vec<TempoSection>,
+	beats: Vec<f32>,
+}
+
+impl TempoAnnotation {
+	pub fn new(sections: Vec<TempoSection>, beats: Vec<f32>) -> Self { Self { sections, beats } }
+
+	/// Annotation of extracted sections with beats on their quarter note grid.
+	pub fn from_sections(sections: Vec<TempoSection>) -> Self {
+		let beats = crate::beat_times(&sections);
+		Self::new(sections, beats)
+	}
+
+	pub fn sections(&self) -> &Vec<TempoSection> { &self.sections }
+
+	pub fn beats(&self) -> &Vec<f32> { &self.beats }
+
+	pub fn duration(&self) -> f32 {
+		self.sections.iter().map(|s| s.end()).chain(self.beats.iter().cloned()).fold(0., f32::max)
+	}
+}
+
+/// Global tempo annotation in MIREX format: the slower tempo, the faster tempo and the salience of the slower one.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MirexTempo {
+	t1: f32,
+	t2: f32,
+	salience: f32,
+}
+
+impl MirexTempo {
+	pub fn new(t1: f32, t2: f32, salience: f32) -> Self { Self { t1, t2, salience } }
+
+	/// Two most prominent tempi by duration ordered by tempo. The salience is the duration share of the slower one.
+	/// The faster tempo is double the only tempo if there is just one.
+	pub fn from_sections(sections: &Vec<TempoSection>) -> Option<Self> {
+		let tempi = crate::tempo_durations(sections);
+
+		let (ta, da) = *tempi.first()?;
+		Some(match tempi.get(1) {
+			Some((tb, db)) if da + db > 0. => {
+				if ta <= *tb { Self::new(ta, *tb, da / (da + db)) } else { Self::new(*tb, ta, db / (da + db)) }
+			},
+			_ => Self::new(ta, ta * 2., 1.),
+		})
+	}
+
+	pub fn t1(&self) -> f32 { self.t1 }
+
+	pub fn t2(&self) -> f32 { self.t2 }
+
+	pub fn salience(&self) -> f32 { self.salience }
+
+	/// The more salient of both tempi.
+	pub fn dominant(&self) -> f32 {
+		if self.salience >= 0.5 { self.t1 } else { self.t2 }
+	}
+
+	/// Single section with the dominant tempo.
+	pub fn to_sections(&self, duration: f32) -> Vec<TempoSection> {
+		vec![TempoSection::new(0., duration, self.dominant(), 0.)]
+	}
+}
+
+pub fn read_jams(path: &Path) -> Result<TempoAnnotation, AnnotationError> {
+	parse_jams(&read_string(path)?)
+}
+
+pub fn save_jams(path: &Path, annotation: &TempoAnnotation) -> Result<(), AnnotationError> {
+	File::create(path)?.write_all(write_jams(annotation).as_bytes())?;
+	Ok(())
+}
+
+/// Reads the first tempo and beat annotation. Section offsets are set to the first beat within the section.
+pub fn parse_jams(data: &str) -> Result<TempoAnnotation, AnnotationError> {
+	let root: Value = serde_json::from_str(data)?;
+	let annotations = root.get("annotations").and_then(|a| a.as_array())
+		.ok_or_else(|| AnnotationError::Format("missing annotations".into()))?;
+	let find = |namespaces: &[&str]| annotations.iter()
+		.find(|a| a.get("namespace").and_then(|n| n.as_str()).map(|n| namespaces.contains(&n)).unwrap_or(false))
+		.map(|a| jams_observations(a))
+		.unwrap_or_else(|| Ok(Vec::new()));
+
+	let mut beats: Vec<f32> = find(&["beat", "beat_position"])?.into_iter().map(|(time, _, _)| time).collect();
+	beats.sort_by(|a, b| a.partial_cmp(b).unwrap_or(Equal));
+
+	let sections = find(&["tempo"])?.into_iter().map(|(time, duration, value)| {
+		let offset = beats.iter().cloned().find(|b| *b >= time && *b < time + duration).unwrap_or(time);
+		TempoSection::new(time, time + duration, value, offset)
+	}).collect();
+
+	Ok(TempoAnnotation::new(sections, beats))
+}
+
+pub fn write_jams(annotation: &TempoAnnotation) -> String {
+	let tempo: Vec<Value> = annotation.sections.iter().map(|s| json!({
+		"time": s.start(), "duration": s.duration(), "value": s.bpm(), "confidence": 1.0
+	})).collect();
+	let beats: Vec<Value> = annotation.beats.iter().enumerate().map(|(i, b)| json!({
+		"time": b, "duration": 0.0, "value": i + 1, "confidence": 1.0
+	})).collect();
+	let annotation_metadata = json!({ "annotation_tools": "littempo", "version": env!("CARGO_PKG_VERSION") });
+
+	let root = json!({
+		"file_metadata": { "duration": annotation.duration(), "jams_version": "0.3.4" },
+		"annotations": [
+			{ "namespace": "tempo", "data": tempo, "annotation_metadata": annotation_metadata.clone(), "sandbox": {} },
+			{ "namespace": "beat", "data": beats, "annotation_metadata": annotation_metadata, "sandbox": {} },
+		],
+		"sandbox": {},
+	});
+	serde_json::to_string_pretty(&root).unwrap()
+}
+
+/// Observations as (time, duration, value). Supports both the sparse (list of observations) and dense (dict of lists) layout.
+fn jams_observations(annotation: &Value) -> Result<Vec<(f32, f32, f32)>, AnnotationError> {
+	let num = |v: Option<&Value>| v.and_then(|v| v.as_f64()).unwrap_or(0.) as f32;
+	match annotation.get("data") {
+		Some(Value::Array(data)) => Ok(data.iter()
+			.map(|o| (num(o.get("time")), num(o.get("duration")), num(o.get("value"))))
+			.collect()),
+		Some(Value::Object(data)) => {
+			let column = |k: &str| data.get(k).and_then(|c| c.as_array()).cloned().unwrap_or_default();
+			let (time, duration, value) = (column("time"), column("duration"), column("value"));
+			Ok(time.iter().enumerate()
+				.map(|(i, t)| (num(Some(t)), num(duration.get(i)), num(value.get(i))))
+				.collect())
+		},
+		_ => Err(AnnotationError::Format("annotation without data".into())),
+	}
+}
+
+pub fn read_section_table(path: &Path) -> Result<Vec<TempoSection>, AnnotationError> {
+	parse_section_table(&read_string(path)?)
+}
+
+/// Parses sections written as csv or tsv (start, end, bpm, offset) by `sections_to_markers`.
+pub fn parse_section_table(data: &str) -> Result<Vec<TempoSection>, AnnotationError> {
+	data.lines()
+		.map(|l| l.trim())
+		.filter(|l| !l.is_empty() && !l.starts_with("start"))
+		.map(|l| {
+			let values = l.split(|c| c == ',' || c == '\t')
+				.map(|v| v.trim().parse::<f32>().map_err(|_| AnnotationError::Format(format!("invalid section '{}'", l))))
+				.collect::<Result<Vec<_>, _>>()?;
+			match values.as_slice() {
+				[start, end, bpm, offset] => Ok(TempoSection::new(*start, *end, *bpm, *offset)),
+				[start, end, bpm] => Ok(TempoSection::new(*start, *end, *bpm, *start)),
+				_ => Err(AnnotationError::Format(format!("invalid section '{}'", l))),
+			}
+		})
+		.collect()
+}
+
+/// Reads sections from a jams file or a csv/tsv section table.
+pub fn read_sections(path: &Path) -> Result<Vec<TempoSection>, AnnotationError> {
+	match path.extension().and_then(|e| e.to_str()) {
+		Some("jams") | Some("json") => Ok(read_jams(path)?.sections),
+		_ => read_section_table(path),
+	}
+}
+
+pub fn read_mirex_beats(path: &Path) -> Result<Vec<f32>, AnnotationError> {
+	parse_mirex_beats(&read_string(path)?)
+}
+
+pub fn save_mirex_beats(path: &Path, beats: &Vec<f32>) -> Result<(), AnnotationError> {
+	File::create(path)?.write_all(write_mirex_beats(beats).as_bytes())?;
+	Ok(())
+}
+
+/// Beat times from the first column of every line. Further columns (e.g. beat numbers) are ignored.
+pub fn parse_mirex_beats(data: &str) -> Result<Vec<f32>, AnnotationError> {
+	data.split(|c| c == '\n' || c == '\r')
+		.filter_map(|l| l.split_whitespace().next())
+		.map(|v| v.parse::<f32>().map_err(|_| AnnotationError::Format(format!("invalid beat time '{}'", v))))
+		.collect()
+}
+
+pub fn write_mirex_beats(beats: &Vec<f32>) -> String {
+	beats.iter().map(|b| format!("{:.6}\n", b)).collect()
+}
+
+pub fn read_mirex_tempo(path: &Path) -> Result<MirexTempo, AnnotationError> {
+	parse_mirex_tempo(&read_string(path)?)
+}
+
+pub fn save_mirex_tempo(path: &Path, tempo: &MirexTempo) -> Result<(), AnnotationError> {
+	File::create(path)?.write_all(write_mirex_tempo(tempo).as_bytes())?;
+	Ok(())
+}
+
+pub fn parse_mirex_tempo(data: &str) -> Result<MirexTempo, AnnotationError> {
+	let values = data.split_whitespace()
+		.map(|v| v.parse::<f32>().map_err(|_| AnnotationError::Format(format!("invalid tempo value '{}'", v))))
+		.collect::<Result<Vec<_>, _>>()?;
+
+	match values.as_slice() {
+		[t1] => Ok(MirexTempo::new(*t1, *t1 * 2., 1.)),
+		[t1, t2] => Ok(MirexTempo::new(*t1, *t2, 0.5)),
+		[t1, t2, salience, ..] => Ok(MirexTempo::new(*t1, *t2, *salience)),
+		_ => Err(AnnotationError::Format("empty tempo annotation".into())),
+	}
+}
+
+pub fn write_mirex_tempo(tempo: &MirexTempo) -> String {
+	format!("{:.2}\t{:.2}\t{:.2}\n", tempo.t1, tempo.t2, tempo.salience)
+}
+
+fn read_string(path: &Path) -> Result<String, AnnotationError> {
+	let mut ret = String::new();
+	File::open(path)?.read_to_string(&mut ret)?;
+	Ok(ret)
+}
@@ -1,60 +1,696 @@
 use litcontainers::*;
 use litaudio::*;
-use std::path::{PathBuf};
-use littempo::TempoExtractionSettings;
-use clap::{App, Arg};
+use std::path::{Path, PathBuf};
+use std::fs::File;
+use std::io::Write;
+use littempo::{TempoExtractionSettings, TempoExtractionSettingsBuilder, TempoAnnotation, TempoSection, MarkerFormat};
+use rayon::prelude::*;
+use clap::{App, Arg, ArgMatches, SubCommand, AppSettings};
 
 fn main() {
-	let (audio_path, settings) = arguments();
-	let audio: AudioDeinterleaved<f64, U1, Dynamic> = litaudioio::read_audio(&audio_path).unwrap();
+	// Diagnostics are controlled with RUST_LOG and written to stderr
+	tracing_subscriber::fmt()
+		.with_writer(std::io::stderr)
+		.with_env_filter(tracing_subscriber::EnvFilter::try_from_default_env().unwrap_or_else(|_| tracing_subscriber::EnvFilter::new("warn")))
+		.init();
 
-	let tempo_sections = littempo::extract_tempo(&audio, &settings);
+	let matches = App::new("LitTempo Extraction")
+		.version("1.0")
+		.author("Egor Dmitriev <egordmitriev2@gmail.com>")
+		.about("Tool to extract tempo information from audio files")
+		.after_help("EXIT CODES:\n    0    Success\n    1    Invalid arguments or IO failure\n    2    Audio could not be read\n    3    No tempo found")
+		.setting(AppSettings::SubcommandRequiredElseHelp)
+		.subcommand(SubCommand::with_name("analyze")
+			.about("Extracts tempo sections")
+			.args(&audio_args())
+			.args(&analysis_args())
+			.arg(Arg::with_name("visualize")
+				.short("v")
+				.help("Creates plots with calculated data"))
+			.arg(Arg::with_name("click")
+				.short("c")
+				.help("Creates audio file with click track"))
+			.arg(Arg::with_name("tui_plot")
+				.long("tui-plot")
+				.help("Plots the novelty curve, cyclic tempogram and sections in the terminal"))
+			.arg(format_arg()))
+		.subcommand(SubCommand::with_name("novelty")
+			.about("Dumps the novelty curve")
+			.args(&audio_args())
+			.args(&analysis_args()))
+		.subcommand(SubCommand::with_name("tempogram")
+			.about("Dumps the magnitude and cyclic tempogram")
+			.args(&audio_args())
+			.args(&analysis_args()))
+		.subcommand(SubCommand::with_name("click")
+			.about("Renders a click track from an existing sections file")
+			.args(&audio_args())
+			.arg(Arg::with_name("sections")
+				.long("sections")
+				.help("Sections file (jams, csv or tsv)")
+				.takes_value(true)
+				.required_unless("print_settings"))
+			.args(&analysis_args()))
+		.subcommand(SubCommand::with_name("beats")
+			.about("Extracts beat times")
+			.args(&audio_args())
+			.args(&analysis_args())
+			.arg(format_arg()))
+		.subcommand(SubCommand::with_name("from-novelty")
+			.about("Extracts tempo sections from a novelty curve csv (time,value)")
+			.args(&external_input_args())
+			.args(&analysis_args()))
+		.subcommand(SubCommand::with_name("from-onsets")
+			.about("Extracts tempo sections from onset times (one per line, optionally followed by a velocity)")
+			.args(&external_input_args())
+			.arg(Arg::with_name("sr")
+				.long("sr")
+				.help("Sample rate of the novelty curve built from the onsets")
+				.takes_value(true)
+				.default_value("100"))
+			.args(&analysis_args()))
+		.subcommand(SubCommand::with_name("batch")
+			.about("Extracts tempo sections of many files in parallel and writes a summary")
+			.arg(Arg::with_name("inputs")
+				.help("Input audio files, directories or glob patterns")
+				.multiple(true)
+				.required_unless("print_settings"))
+			.arg(Arg::with_name("output")
+				.short("o")
+				.help("Output directory. Results mirror the input paths relative to their common directory")
+				.takes_value(true))
+			.arg(Arg::with_name("jobs")
+				.short("j")
+				.long("jobs")
+				.help("Amount of files processed at the same time (at least 1)")
+				.takes_value(true)
+				.default_value("4"))
+			.arg(Arg::with_name("extensions")
+				.long("extensions")
+				.help("Audio file extensions picked up from directories")
+				.takes_value(true)
+				.default_value("wav,mp3,flac,ogg"))
+			.arg(Arg::with_name("recursive")
+				.short("r")
+				.help("Also searches subdirectories"))
+			.args(&analysis_args()))
+		.subcommand(SubCommand::with_name("eval")
+			.about("Compares estimated sections or beats against reference annotations")
+			.arg(Arg::with_name("estimated")
+				.help("Estimated sections (jams, csv, tsv) or beats (txt)")
+				.required(true))
+			.arg(Arg::with_name("reference")
+				.help("Reference annotations (jams, csv, tsv) or beats (txt)")
+				.required(true))
+			.arg(Arg::with_name("output")
+				.short("o")
+				.help("Output directory")
+				.takes_value(true)))
+		.get_matches();
 
-	println!("Found tempo sections:");
+	if let (_, Some(m)) = matches.subcommand() {
+		if m.is_present("print_settings") { print_settings(m); }
+	}
+
+	match matches.subcommand() {
+		("analyze", Some(m)) => analyze(m),
+		("novelty", Some(m)) => novelty(m),
+		("tempogram", Some(m)) => tempogram(m),
+		("click", Some(m)) => click(m),
+		("beats", Some(m)) => beats(m),
+		("from-novelty", Some(m)) => from_novelty(m),
+		("from-onsets", Some(m)) => from_onsets(m),
+		("batch", Some(m)) => batch(m),
+		("eval", Some(m)) => eval(m),
+		_ => unreachable!(),
+	}
+}
+
+const EXIT_FAILURE: i32 = 1;
+const EXIT_UNREADABLE_AUDIO: i32 = 2;
+const EXIT_NO_TEMPO: i32 = 3;
+
+fn analyze(m: &ArgMatches) {
+	let (audio, out_path) = read_input(m);
+	let format = m.value_of("format");
+	let to_stdout = format.is_some() && m.value_of("output").is_none();
+
+	let mut builder = settings_builder(m);
+	builder
+		.save_click_track(m.is_present("click"))
+		.visualize(m.is_present("visualize"))
+		.save_path(out_path.clone());
+	let settings = build_settings(&builder);
+	if !to_stdout || m.is_present("click") || m.is_present("visualize") { create_dir(&out_path); }
+
+	let extraction = littempo::extract_tempo_detailed(&audio, &settings, &littempo::PrintProgress).unwrap();
+	if m.is_present("tui_plot") {
+		let width = std::env::var("COLUMNS").ok().and_then(|v| v.parse().ok()).unwrap_or(100);
+		let plot = littempo::render_terminal_plot(
+			extraction.novelty_curve(), *extraction.nc_sr(), extraction.analysis().as_ref(), extraction.sections(), width, 12
+		);
+		// Keep stdout clean if the result is written there
+		if to_stdout { eprint!("{}", plot) } else { print!("{}", plot) }
+	}
+	let tempo_sections = extraction.sections().clone();
+	check_tempo(&tempo_sections);
+	eprintln!("Found {} tempo sections", tempo_sections.len());
 	for s in &tempo_sections {
-		println!("{:#?}", s);
+		eprintln!(" - {:.2}s - {:.2}s: {:.2} BPM (offset {:.3}s)", s.start(), s.end(), s.bpm(), s.offset());
+	}
+
+	match format {
+		Some(format) => {
+			let data = format_sections(&tempo_sections, format);
+			if to_stdout {
+				std::io::stdout().write_all(&data).unwrap_or_else(|e| fail(&e.to_string()));
+			} else {
+				let path = out_path.join(format!("sections.{}", format_extension(format)));
+				File::create(&path).and_then(|mut f| f.write_all(&data))
+					.unwrap_or_else(|e| fail(&format!("Could not write {}: {}", path.display(), e)));
+			}
+		},
+		None => {
+			littempo::save_section_markers(&out_path.join("sections.csv"), &tempo_sections, MarkerFormat::Csv)
+				.unwrap_or_else(|e| fail(&e.to_string()));
+			littempo::save_jams(&out_path.join("sections.jams"), &TempoAnnotation::from_sections(tempo_sections))
+				.unwrap_or_else(|e| fail(&e.to_string()));
+		},
 	}
 }
 
-fn arguments() -> (PathBuf, TempoExtractionSettings) {
-	let matches = App::new("LitTempo Extraction")
-		.version("1.0")
-		.author("Egor Dmitriev <egordmitriev2@gmail.com>")
-		.about("Tool to extract tempo information from audio files")
-		.arg(Arg::with_name("input")
+fn novelty(m: &ArgMatches) {
+	let (audio, out_path) = read_input(m);
+	let settings = build_settings(&settings_builder(m));
+	create_dir(&out_path);
+
+	let (novelty_curve, nc_sr) = littempo::extract_novelty_curve(&audio, &settings, &littempo::PrintProgress).unwrap();
+
+	let mut data = String::from("time,value\n");
+	for (i, v) in novelty_curve.as_iter().enumerate() {
+		data.push_str(&format!("{:.6},{:.8}\n", i as f64 / nc_sr, v));
+	}
+	write_file(&out_path.join("novelty_curve.csv"), &data);
+}
+
+fn tempogram(m: &ArgMatches) {
+	let (audio, out_path) = read_input(m);
+	let settings = build_settings(&settings_builder(m));
+	create_dir(&out_path);
+
+	let (novelty_curve, nc_sr) = littempo::extract_novelty_curve(&audio, &settings, &littempo::PrintProgress).unwrap();
+	let analysis = littempo::analyze_tempogram(&novelty_curve, nc_sr, &settings, &littempo::PrintProgress).unwrap();
+
+	write_file(&out_path.join("tempogram.csv"), &matrix_csv(analysis.tempogram_mag(), analysis.bpms(), *analysis.sr()));
+	write_file(&out_path.join("cyclic_tempogram.csv"), &matrix_csv(analysis.cyclic_tempogram(), analysis.cyclic_tempogram_axis(), *analysis.sr()));
+}
+
+fn click(m: &ArgMatches) {
+	let (audio, out_path) = read_input(m);
+	let settings = build_settings(&settings_builder(m));
+	create_dir(&out_path);
+	let sections = littempo::read_sections(Path::new(m.value_of("sections").unwrap()))
+		.unwrap_or_else(|e| fail(&e.to_string()));
+
+	let mut click_audio = AudioDeinterleaved::new(DeinterleavedStorage::zeros(audio.channel_dim(), audio.sample_dim()), audio.sample_rate());
+	click_audio.as_iter_mut().zip(audio.as_iter()).for_each(|(o, i)| *o = *i as f32);
+	littempo::save_tempo_click_track(&out_path.join("click_track.mp3"), &click_audio, &sections, *settings.click_fraction(), settings.click_track_options())
+		.unwrap_or_else(|e| fail(&format!("{:?}", e)));
+}
+
+fn beats(m: &ArgMatches) {
+	let (audio, out_path) = read_input(m);
+	let settings = build_settings(&settings_builder(m));
+
+	let tempo_sections = littempo::extract_tempo_observed(&audio, &settings, &littempo::PrintProgress).unwrap();
+	check_tempo(&tempo_sections);
+	let beats = littempo::beat_times(&tempo_sections);
+
+	match (m.value_of("format"), m.value_of("output")) {
+		(Some("midi"), _) => fail("Beats can not be exported as midi, use analyze instead"),
+		(Some(format), None) => print!("{}", littempo::beats_to_markers(&beats, marker_format(format))),
+		(Some(format), Some(_)) => {
+			create_dir(&out_path);
+			littempo::save_beat_markers(&out_path.join(format!("beats.{}", format_extension(format))), &beats, marker_format(format))
+				.unwrap_or_else(|e| fail(&e.to_string()));
+		},
+		(None, _) => {
+			create_dir(&out_path);
+			littempo::save_mirex_beats(&out_path.join("beats.txt"), &beats)
+				.unwrap_or_else(|e| fail(&e.to_string()));
+		},
+	}
+}
+
+fn format_arg<'a, 'b>() -> Arg<'a, 'b> {
+	Arg::with_name("format")
+		.long("format")
+		.help("Output format. Written to stdout unless an output directory is given")
+		.takes_value(true)
+		.possible_values(&["json", "csv", "osu", "midi", "labels"])
+}
+
+fn marker_format(format: &str) -> MarkerFormat {
+	match format {
+		"json" => MarkerFormat::Json,
+		"osu" => MarkerFormat::Osu,
+		"labels" => MarkerFormat::Audacity,
+		_ => MarkerFormat::Csv,
+	}
+}
+
+fn format_extension(format: &str) -> &'static str {
+	match format {
+		"json" => "json",
+		"osu" => "osu",
+		"midi" => "mid",
+		"labels" => "txt",
+		_ => "csv",
+	}
+}
+
+fn format_sections(sections: &Vec<TempoSection>, format: &str) -> Vec<u8> {
+	match format {
+		"midi" => littempo::tempo_sections_to_midi(sections, &littempo::MidiExportSettings::default()),
+		format => littempo::sections_to_markers(sections, marker_format(format)).into_bytes(),
+	}
+}
+
+/// Exits with a distinct code if no usable tempo was found.
+fn check_tempo(sections: &Vec<TempoSection>) {
+	if sections.iter().all(|s| !(s.bpm() > 0.) || !s.bpm().is_finite()) {
+		exit_with(EXIT_NO_TEMPO, "No tempo found");
+	}
+}
+
+fn from_novelty(m: &ArgMatches) {
+	let settings = build_settings(&settings_builder(m));
+	let data = read_text(Path::new(m.value_of("input").unwrap()));
+	let rows = parse_rows(&data);
+	if rows.iter().any(|r| r.len() < 2) { fail("Novelty curve needs time,value rows"); }
+
+	let times: Vec<f64> = rows.iter().map(|r| r[0]).collect();
+	let values: Vec<f64> = rows.iter().map(|r| r[1]).collect();
+	let (novelty_curve, nc_sr) = littempo::timed_novelty_curve(&times, &values).unwrap_or_else(|e| fail(&e));
+
+	let sections = littempo::extract_tempo_from_novelty_curve(&novelty_curve, nc_sr, &settings, &littempo::PrintProgress).unwrap();
+	write_sections(m, &sections);
+}
+
+fn from_onsets(m: &ArgMatches) {
+	let settings = build_settings(&settings_builder(m));
+	let sr = m.value_of("sr").unwrap().parse().unwrap_or_else(|_| fail("Invalid value for sr"));
+	let data = read_text(Path::new(m.value_of("input").unwrap()));
+	let rows = parse_rows(&data);
+	let onsets: Vec<f32> = rows.iter().map(|r| r[0] as f32).collect();
+	let velocities: Vec<f32> = rows.iter().map(|r| r.get(1).cloned().unwrap_or(1.) as f32).collect();
+
+	let novelty_curve = littempo::onsets_to_novelty_curve(&onsets, Some(&velocities), sr, None);
+	let sections = littempo::extract_tempo_from_novelty_curve(&novelty_curve, sr, &settings, &littempo::PrintProgress).unwrap();
+	write_sections(m, &sections);
+}
+
+fn external_input_args<'a, 'b>() -> Vec<Arg<'a, 'b>> {
+	vec![
+		Arg::with_name("input")
+			.help("Input file")
+			.required_unless("print_settings"),
+		Arg::with_name("output")
+			.short("o")
+			.help("Output directory. Sections are written to stdout if not given")
+			.takes_value(true),
+		format_arg().default_value("csv"),
+	]
+}
+
+/// Writes the sections in the requested format to the output directory or stdout.
+fn write_sections(m: &ArgMatches, sections: &Vec<TempoSection>) {
+	check_tempo(sections);
+	let format = m.value_of("format").unwrap_or("csv");
+	let data = format_sections(sections, format);
+	match m.value_of("output") {
+		Some(output) => {
+			let out_path = PathBuf::from(output);
+			create_dir(&out_path);
+			let path = out_path.join(format!("sections.{}", format_extension(format)));
+			File::create(&path).and_then(|mut f| f.write_all(&data))
+				.unwrap_or_else(|e| fail(&format!("Could not write {}: {}", path.display(), e)));
+		},
+		None => std::io::stdout().write_all(&data).unwrap_or_else(|e| fail(&e.to_string())),
+	}
+}
+
+fn read_text(path: &Path) -> String {
+	std::fs::read_to_string(path).unwrap_or_else(|e| fail(&format!("Could not read {}: {}", path.display(), e)))
+}
+
+/// Numeric rows separated by commas, tabs or spaces. Skips comments and headers.
+fn parse_rows(data: &str) -> Vec<Vec<f64>> {
+	data.lines()
+		.map(|l| l.trim())
+		.filter(|l| !l.is_empty() && !l.starts_with('#'))
+		.filter_map(|l| l.split(|c: char| c == ',' || c.is_whitespace())
+			.filter(|v| !v.is_empty())
+			.map(|v| v.parse().ok())
+			.collect::<Option<Vec<f64>>>())
+		.filter(|r| !r.is_empty())
+		.collect()
+}
+
+struct BatchResult {
+	file: PathBuf,
+	global_bpm: Option<f32>,
+	section_count: usize,
+	time: f64,
+	error: Option<String>,
+}
+
+fn batch(m: &ArgMatches) {
+	let extensions: Vec<&str> = m.value_of("extensions").unwrap().split(',').collect();
+	let files = collect_inputs(m.values_of("inputs").unwrap(), &extensions, m.is_present("recursive"));
+	let out_dirs = batch_output_dirs(&files);
+	let jobs: usize = m.value_of("jobs").unwrap().parse().unwrap_or_else(|_| fail("Invalid value for jobs"));
+	if jobs == 0 { fail("Jobs must be at least 1"); }
+	let builder = settings_builder(m);
+	build_settings(&builder);
+
+	let out_root = m.value_of("output").map(PathBuf::from)
+		.unwrap_or_else(|| std::env::current_dir().unwrap());
+	std::fs::create_dir_all(&out_root).unwrap_or_else(|e| fail(&e.to_string()));
+
+	// Each worker holds a decoded track in memory, so the pool size bounds memory usage.
+	let pool = rayon::ThreadPoolBuilder::new().num_threads(jobs).build()
+		.unwrap_or_else(|e| fail(&e.to_string()));
+	let done = std::sync::atomic::AtomicUsize::new(0);
+	let results: Vec<BatchResult> = pool.install(|| {
+		files.par_iter().zip(out_dirs.par_iter()).map(|(file, out_dir)| {
+			let ret = batch_file(file, &out_root.join(out_dir), &builder);
+			let i = done.fetch_add(1, std::sync::atomic::Ordering::SeqCst) + 1;
+			match &ret.error {
+				Some(e) => eprintln!("[{}/{}] {}: {}", i, files.len(), file.display(), e),
+				None => eprintln!("[{}/{}] {}", i, files.len(), file.display()),
+			}
+			ret
+		}).collect()
+	});
+
+	write_file(&out_root.join("summary.csv"), &batch_summary_csv(&results));
+	write_file(&out_root.join("summary.json"), &batch_summary_json(&results));
+	let failed = results.iter().filter(|r| r.error.is_some()).count();
+	eprintln!("Processed {} files, {} failed", results.len(), failed);
+	if failed > 0 { std::process::exit(EXIT_FAILURE); }
+}
+
+/// Expands directories and glob patterns into a sorted list of audio files.
+fn collect_inputs<'a>(inputs: impl Iterator<Item=&'a str>, extensions: &[&str], recursive: bool) -> Vec<PathBuf> {
+	let mut ret = Vec::new();
+	for input in inputs {
+		let path = PathBuf::from(input);
+		if path.is_dir() {
+			collect_directory(&path, extensions, recursive, &mut ret);
+		} else if path.is_file() {
+			ret.push(path);
+		} else {
+			let paths = glob::glob(input).unwrap_or_else(|e| fail(&format!("Invalid pattern {}: {}", input, e)));
+			ret.extend(paths.filter_map(Result::ok).filter(|p| p.is_file()));
+		}
+	}
+	ret.sort();
+	ret.dedup();
+	ret
+}
+
+fn collect_directory(dir: &Path, extensions: &[&str], recursive: bool, files: &mut Vec<PathBuf>) {
+	let entries = std::fs::read_dir(dir).unwrap_or_else(|e| fail(&format!("Could not read {}: {}", dir.display(), e)));
+	for path in entries.filter_map(Result::ok).map(|e| e.path()) {
+		if path.is_dir() {
+			if recursive { collect_directory(&path, extensions, recursive, files); }
+		} else if path.extension().and_then(|e| e.to_str()).map(|e| extensions.contains(&e.to_lowercase().as_str())).unwrap_or(false) {
+			files.push(path);
+		}
+	}
+}
+
+/// Output directory of every file: its path without extension relative to the deepest directory containing all files.
+/// Fails if two files would share a directory (e.g. same name with different extensions).
+fn batch_output_dirs(files: &[PathBuf]) -> Vec<PathBuf> {
+	let paths: Vec<PathBuf> = files.iter()
+		.map(|f| std::fs::canonicalize(f).unwrap_or_else(|e| fail(&format!("Could not resolve {}: {}", f.display(), e))))
+		.collect();
+	let mut root = paths.first().and_then(|p| p.parent()).map(Path::to_path_buf).unwrap_or_default();
+	while !paths.iter().all(|p| p.starts_with(&root)) {
+		if !root.pop() { break; }
+	}
+
+	let ret: Vec<PathBuf> = paths.iter().map(|p| p.strip_prefix(&root).unwrap_or(p).with_extension("")).collect();
+	let mut sorted: Vec<(&PathBuf, &PathBuf)> = ret.iter().zip(files.iter()).collect();
+	sorted.sort();
+	if let Some(w) = sorted.windows(2).find(|w| w[0].0 == w[1].0) {
+		fail(&format!("{} and {} would both be written to {}", w[0].1.display(), w[1].1.display(), w[0].0.display()));
+	}
+	ret
+}
+
+fn batch_file(path: &Path, out_path: &Path, builder: &TempoExtractionSettingsBuilder) -> BatchResult {
+	let start = std::time::Instant::now();
+	let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| -> Result<Vec<TempoSection>, String> {
+		std::fs::create_dir_all(out_path).map_err(|e| e.to_string())?;
+		let settings = builder.clone().save_path(out_path.to_path_buf()).build()?;
+
+		let audio = litaudioio::read_audio(path).map_err(|e| format!("Could not read audio: {:?}", e))?;
+		let tempo_sections = littempo::extract_tempo(&audio, &settings);
+
+		littempo::save_section_markers(&out_path.join("sections.csv"), &tempo_sections, MarkerFormat::Csv)
+			.map_err(|e| e.to_string())?;
+		littempo::save_jams(&out_path.join("sections.jams"), &TempoAnnotation::from_sections(tempo_sections.clone()))
+			.map_err(|e| e.to_string())?;
+		Ok(tempo_sections)
+	})).unwrap_or_else(|_| Err("Extraction panicked".to_string()));
+
+	let (global_bpm, section_count, error) = match result {
+		Ok(sections) => (littempo::global_bpm(&sections), sections.len(), None),
+		Err(e) => (None, 0, Some(e)),
+	};
+	BatchResult { file: path.to_path_buf(), global_bpm, section_count, time: start.elapsed().as_secs_f64(), error }
+}
+
+fn batch_summary_csv(results: &[BatchResult]) -> String {
+	let mut ret = String::from("file,global_bpm,sections,time,error\n");
+	for r in results {
+		ret.push_str(&format!(
+			"\"{}\",{},{},{:.3},\"{}\"\n",
+			r.file.display().to_string().replace('"', "\"\""),
+			r.global_bpm.map(|v| v.to_string()).unwrap_or_default(),
+			r.section_count,
+			r.time,
+			r.error.clone().unwrap_or_default().replace('"', "\"\""),
+		));
+	}
+	ret
+}
+
+fn batch_summary_json(results: &[BatchResult]) -> String {
+	let entries: Vec<_> = results.iter().map(|r| serde_json::json!({
+		"file": r.file.display().to_string(),
+		"global_bpm": r.global_bpm,
+		"sections": r.section_count,
+		"time": r.time,
+		"error": r.error,
+	})).collect();
+	serde_json::to_string_pretty(&entries).unwrap()
+}
+
+fn eval(m: &ArgMatches) {
+	let estimated = read_annotation(Path::new(m.value_of("estimated").unwrap()));
+	let reference = read_annotation(Path::new(m.value_of("reference").unwrap()));
+	let settings = littempo::EvaluationSettings::default();
+
+	let tempo = littempo::evaluate_tempo(estimated.sections(), reference.sections(), &settings);
+	let beats = littempo::evaluate_beats(estimated.beats(), reference.beats(), &settings);
+
+	let data = format!(
+		"accuracy1,accuracy2,f_measure,cemgil,p_score,cml_c,cml_t,aml_c,aml_t,information_gain\n{},{},{},{},{},{},{},{},{},{}\n",
+		tempo.accuracy1(), tempo.accuracy2(), beats.f_measure(), beats.cemgil(), beats.p_score(),
+		beats.cml_c(), beats.cml_t(), beats.aml_c(), beats.aml_t(), beats.information_gain()
+	);
+	print!("{}", data);
+	if let Some(output) = m.value_of("output") {
+		let out_path = PathBuf::from(output);
+		std::fs::create_dir_all(&out_path).unwrap_or_else(|e| fail(&e.to_string()));
+		write_file(&out_path.join("evaluation.csv"), &data);
+	}
+}
+
+fn audio_args<'a, 'b>() -> Vec<Arg<'a, 'b>> {
+	vec![
+		Arg::with_name("input")
 			.help("Input audio file")
-			.required(true))
-		.arg(Arg::with_name("output")
+			.required_unless("print_settings"),
+		Arg::with_name("output")
 			.short("o")
 			.help("Output directory")
-			.takes_value(true))
-		.arg(Arg::with_name("visualize")
-			.short("v")
-			.help("Creates plots with calculated data"))
-		.arg(Arg::with_name("click")
-			.short("c")
-			.help("Creates audio file with click track"))
-		.get_matches();
-	let default_settings = littempo::TempoExtractionSettingsBuilder::default().build().unwrap();
+			.takes_value(true),
+	]
+}
+
+fn analysis_args<'a, 'b>() -> Vec<Arg<'a, 'b>> {
+	let mut ret: Vec<Arg> = littempo::SETTING_FIELDS.iter()
+		.filter(|(name, _, _)| !DEDICATED_SETTINGS.contains(name))
+		.map(|(name, long, help)| {
+			Arg::with_name(name)
+				.long(long)
+				.help(help)
+				.takes_value(true)
+		}).collect();
+	ret.push(Arg::with_name("bpm_range")
+		.long("bpm-range")
+		.help("Known bpm range as min:max")
+		.takes_value(true));
+	ret.push(Arg::with_name("fixed_bpm")
+		.long("fixed-bpm")
+		.help("Known bpm, only the offset is estimated")
+		.takes_value(true));
+	ret.push(Arg::with_name("refine_sections")
+		.long("refine-sections")
+		.help("Sections file (jams, csv or tsv) to refine instead of discovering sections")
+		.takes_value(true));
+	ret.push(Arg::with_name("lock")
+		.long("lock")
+		.help("Fields of the refined sections to keep")
+		.takes_value(true)
+		.use_delimiter(true)
+		.possible_values(&["boundaries", "bpm", "offset"]));
+	ret.push(Arg::with_name("preset")
+		.long("preset")
+		.help("Toml file with settings. Command line flags take precedence")
+		.takes_value(true));
+	ret.push(Arg::with_name("print_settings")
+		.long("print-settings")
+		.help("Prints the effective settings as preset and exits"));
+	ret
+}
+
+/// Settings controlled by subcommand specific flags.
+const DEDICATED_SETTINGS: &[&str] = &["verbose", "visualize", "save_click_track", "save_path"];
+
+/// Defaults overridden by the preset and then by the command line flags.
+fn settings_builder(m: &ArgMatches) -> TempoExtractionSettingsBuilder {
+	let mut builder = match m.value_of("preset") {
+		Some(path) => littempo::read_preset(Path::new(path)).unwrap_or_else(|e| fail(&e.to_string())),
+		None => TempoExtractionSettingsBuilder::default(),
+	};
+	for (name, _, _) in littempo::SETTING_FIELDS {
+		if DEDICATED_SETTINGS.contains(name) { continue; }
+		if let Some(v) = m.value_of(name) {
+			littempo::apply_setting(&mut builder, name, v).unwrap_or_else(|e| fail(&e.to_string()));
+		}
+	}
+	if let Some(constraints) = constraints(m) { builder.constraints(constraints); }
+	builder
+}
+
+fn constraints(m: &ArgMatches) -> Option<littempo::TempoConstraints> {
+	if !["bpm_range", "fixed_bpm", "refine_sections", "lock"].iter().any(|name| m.is_present(name)) { return None; }
 
-	let path = PathBuf::from(matches.value_of("input").unwrap());
-	assert!(path.exists(), "Input file doesnt exist!");
+	let mut builder = littempo::TempoConstraintsBuilder::default();
+	if let Some(range) = m.value_of("bpm_range") {
+		let values: Vec<f32> = range.split(':').map(|v| v.parse().unwrap_or_else(|_| fail("Invalid value for bpm-range"))).collect();
+		if values.len() != 2 || values[0] > values[1] { fail("Invalid value for bpm-range, expected min:max"); }
+		builder.bpm_range(Some((values[0], values[1])));
+	}
+	if let Some(bpm) = m.value_of("fixed_bpm") {
+		builder.fixed_bpm(Some(bpm.parse().unwrap_or_else(|_| fail("Invalid value for fixed-bpm"))));
+	}
+	if let Some(path) = m.value_of("refine_sections") {
+		builder.sections(littempo::read_sections(Path::new(path)).unwrap_or_else(|e| fail(&e.to_string())));
+	}
+	let locked: Vec<&str> = m.values_of("lock").map(|v| v.collect()).unwrap_or_default();
+	builder.locked(littempo::LockedFields {
+		boundaries: locked.contains(&"boundaries"),
+		bpm: locked.contains(&"bpm"),
+		offset: locked.contains(&"offset"),
+	});
+	Some(builder.build().unwrap_or_else(|e| fail(&e)))
+}
+
+fn build_settings(builder: &TempoExtractionSettingsBuilder) -> TempoExtractionSettings {
+	builder.build().unwrap_or_else(|e| fail(&e))
+}
+
+/// Prints the effective settings as preset. Needs no input.
+fn print_settings(m: &ArgMatches) -> ! {
+	let mut builder = settings_builder(m);
+	builder
+		.save_click_track(m.is_present("click"))
+		.visualize(m.is_present("visualize"));
+	if let Some(output) = m.value_of("output") { builder.save_path(PathBuf::from(output)); }
+	print!("{}", littempo::settings_to_preset(&build_settings(&builder)));
+	std::process::exit(0)
+}
+
+fn read_input(m: &ArgMatches) -> (AudioDeinterleaved<f64, U1, Dynamic>, PathBuf) {
+	let default_settings = TempoExtractionSettingsBuilder::default().build().unwrap();
+
+	let path = PathBuf::from(m.value_of("input").unwrap());
+	if !path.exists() { exit_with(EXIT_UNREADABLE_AUDIO, "Input file doesnt exist!"); }
 
 	let filename = (&path).file_stem().unwrap().to_str().unwrap().to_string();
-	let out_path = matches.value_of("output").map(|s| PathBuf::from(s))
+	let out_path = m.value_of("output").map(|s| PathBuf::from(s))
 		.unwrap_or(default_settings.save_path().clone())
 		.join(filename);
 
-	if !out_path.exists() {
-		std::fs::create_dir_all(&out_path).unwrap();
+	let audio = litaudioio::read_audio(&path)
+		.unwrap_or_else(|e| exit_with(EXIT_UNREADABLE_AUDIO, &format!("Could not read audio: {:?}", e)));
+	(audio, out_path)
+}
+
+fn create_dir(path: &Path) {
+	std::fs::create_dir_all(path).unwrap_or_else(|e| fail(&e.to_string()));
+}
+
+/// Sections with beats on their grid or plain beats if the file is a beat list.
+fn read_annotation(path: &Path) -> TempoAnnotation {
+	let ret = match path.extension().and_then(|e| e.to_str()) {
+		Some("txt") | Some("beats") => littempo::read_mirex_beats(path).map(|b| TempoAnnotation::new(Vec::new(), b)),
+		Some("jams") | Some("json") => littempo::read_jams(path),
+		_ => littempo::read_sections(path).map(TempoAnnotation::from_sections),
+	};
+	let ret = ret.unwrap_or_else(|e| fail(&e.to_string()));
+	if ret.beats().is_empty() {
+		TempoAnnotation::from_sections(ret.sections().clone())
+	} else {
+		ret
+	}
+}
+
+fn matrix_csv<R: Dim, C: Dim, A: Dim>(s: &ContainerRM<f64, R, C>, axis: &RowVec<f64, A>, sr: f64) -> String {
+	let mut ret = String::from("time");
+	for v in axis.as_iter() {
+		ret.push_str(&format!(",{}", v));
+	}
+	ret.push('\n');
+
+	let rows: Vec<Vec<f64>> = s.as_row_slice_iter().map(|r| r.as_iter().cloned().collect()).collect();
+	for ci in 0..s.col_count() {
+		ret.push_str(&format!("{:.6}", ci as f64 / sr));
+		for row in &rows {
+			ret.push_str(&format!(",{:.8}", row[ci]));
+		}
+		ret.push('\n');
 	}
+	ret
+}
 
-	let settings_builder = littempo::TempoExtractionSettingsBuilder::default()
-		.save_click_track(matches.is_present("click"))
-		.visualize(matches.is_present("visualize"))
-		.save_path(out_path)
-		.verbose(true)
-		.build().unwrap();
+fn write_file(path: &Path, data: &str) {
+	File::create(path).and_then(|mut f| f.write_all(data.as_bytes()))
+		.unwrap_or_else(|e| fail(&format!("Could not write {}: {}", path.display(), e)));
+}
+
+fn fail(message: &str) -> ! {
+	exit_with(EXIT_FAILURE, message)
+}
 
-	(path, settings_builder)
-}
\ No newline at end of file
+fn exit_with(code: i32, message: &str) -> ! {
+	eprintln!("{}", message);
+	std::process::exit(code)
+}
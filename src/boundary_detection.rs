@@ -0,0 +1,186 @@
+use litcontainers::*;
+use crate::TempoSection;
+use std::cmp::Ordering::Equal;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BoundaryMethod {
+	/// Gaussian tapered checkerboard kernel correlated along the diagonal of the self-similarity matrix.
+	Checkerboard,
+	/// Distance between the mean tempogram columns before and after each position (using cumulative sums).
+	CumulativeSum,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct BoundaryCandidate {
+	time: f32,
+	strength: f32,
+}
+
+impl BoundaryCandidate {
+	pub fn new(time: f32, strength: f32) -> Self { Self { time, strength } }
+
+	pub fn time(&self) -> f32 { self.time }
+
+	pub fn strength(&self) -> f32 { self.strength }
+}
+
+/// Detects structural changes in the tempogram. Kernel length is the amount of columns looked at on each side.
+pub fn detect_tempo_boundaries<R, C, S>(s: &S, sr: f64, kernel_length: usize, method: BoundaryMethod, min_strength: f64)
+	-> Vec<BoundaryCandidate>
+	where R: Dim, C: Dim, S: Storage<f64, R, C>
+{
+	let novelty = match method {
+		BoundaryMethod::Checkerboard => banded_checkerboard_novelty(s, kernel_length),
+		BoundaryMethod::CumulativeSum => cumulative_sum_novelty(s, kernel_length),
+	};
+
+	pick_boundaries(&novelty, sr, kernel_length, min_strength)
+}
+
+/// Cosine similarity between all tempogram columns. Quadratic in the column count, see banded_checkerboard_novelty.
+pub fn self_similarity_matrix<R, C, S>(s: &S) -> ContainerRM<f64, C, C>
+	where R: Dim, C: Dim, S: Storage<f64, R, C>
+{
+	let cols = normalized_columns(s);
+	let mut ret = ContainerRM::zeros(s.col_dim(), s.col_dim());
+	for (i, a) in cols.iter().enumerate() {
+		for (j, b) in cols.iter().enumerate().skip(i) {
+			let v: f64 = a.iter().zip(b.iter()).map(|(x, y)| x * y).sum();
+			*ret.get_mut(i, j) = v;
+			*ret.get_mut(j, i) = v;
+		}
+	}
+	ret
+}
+
+pub fn checkerboard_novelty<C, S>(ssm: &S, kernel_length: usize) -> RowVec<f64, Dynamic>
+	where C: Dim, S: Storage<f64, C, C>
+{
+	let rows: Vec<Vec<f64>> = ssm.as_row_slice_iter().map(|r| r.as_iter().cloned().collect()).collect();
+	checkerboard_novelty_with(ssm.col_count(), kernel_length, |a, b| rows[a][b])
+}
+
+/// Same as checkerboard_novelty of the self-similarity matrix but only computes the similarities
+/// within the kernel band around the diagonal.
+pub fn banded_checkerboard_novelty<R, C, S>(s: &S, kernel_length: usize) -> RowVec<f64, Dynamic>
+	where R: Dim, C: Dim, S: Storage<f64, R, C>
+{
+	let cols = normalized_columns(s);
+	let width = 2 * kernel_length.max(1);
+	// Similarity of every column with the following columns within the band
+	let band: Vec<Vec<f64>> = (0..cols.len()).map(|a| {
+		(a..(a + width).min(cols.len()))
+			.map(|b| cols[a].iter().zip(cols[b].iter()).map(|(x, y)| x * y).sum())
+			.collect()
+	}).collect();
+	checkerboard_novelty_with(cols.len(), kernel_length, |a, b| band[a.min(b)][a.max(b) - a.min(b)])
+}
+
+fn checkerboard_novelty_with(n: usize, kernel_length: usize, similarity: impl Fn(usize, usize) -> f64) -> RowVec<f64, Dynamic> {
+	let l = kernel_length.max(1) as i64;
+	let sigma = 0.5 * l as f64;
+
+	let mut kernel = Vec::new();
+	for i in -l..l {
+		for j in -l..l {
+			// Offsets are centered between two columns
+			let (x, y) = (i as f64 + 0.5, j as f64 + 0.5);
+			let sign = if (i < 0) == (j < 0) { 1. } else { -1. };
+			kernel.push((i, j, sign * (-(x * x + y * y) / (2. * sigma * sigma)).exp()));
+		}
+	}
+	let kernel_sum: f64 = kernel.iter().map(|(_, _, k)| k.abs()).sum();
+
+	let novelty: Vec<f64> = (0..n as i64).map(|t| {
+		kernel.iter()
+			.filter(|(i, j, _)| t + i >= 0 && t + i < n as i64 && t + j >= 0 && t + j < n as i64)
+			.map(|(i, j, k)| k * similarity((t + i) as usize, (t + j) as usize))
+			.sum::<f64>().max(0.) / kernel_sum
+	}).collect();
+
+	ContainerRM::from_vec(U1, D!(n), &novelty)
+}
+
+pub fn cumulative_sum_novelty<R, C, S>(s: &S, kernel_length: usize) -> RowVec<f64, Dynamic>
+	where R: Dim, C: Dim, S: Storage<f64, R, C>
+{
+	let cols = normalized_columns(s);
+	let n = cols.len();
+	let dims = s.row_count();
+
+	// Cumulative sums of the columns allow constant time window means
+	let mut cumsum = vec![vec![0.; dims]; n + 1];
+	for (i, col) in cols.iter().enumerate() {
+		for d in 0..dims {
+			cumsum[i + 1][d] = cumsum[i][d] + col[d];
+		}
+	}
+
+	let novelty: Vec<f64> = (0..n).map(|t| {
+		let (start, end) = (t.saturating_sub(kernel_length.max(1)), (t + kernel_length.max(1)).min(n));
+		let (nl, nr) = ((t - start) as f64, (end - t) as f64);
+		if nl == 0. || nr == 0. { return 0.; }
+
+		let distance: f64 = (0..dims).map(|d| {
+			let left = (cumsum[t][d] - cumsum[start][d]) / nl;
+			let right = (cumsum[end][d] - cumsum[t][d]) / nr;
+			(left - right).powi(2)
+		}).sum::<f64>().sqrt();
+		// Scale by the amount of evidence on both sides
+		distance * (nl * nr / (nl + nr) / (kernel_length.max(1) as f64 / 2.)).sqrt()
+	}).collect();
+
+	ContainerRM::from_vec(U1, D!(n), &novelty)
+}
+
+/// Local maxima of the novelty curve which are at least min_distance columns apart.
+pub fn pick_boundaries<C, S>(novelty: &S, sr: f64, min_distance: usize, min_strength: f64) -> Vec<BoundaryCandidate>
+	where C: Dim, S: RowVecStorage<f64, C>
+{
+	let values: Vec<f64> = novelty.as_iter().cloned().collect();
+	let mut peaks: Vec<usize> = (0..values.len())
+		.filter(|i| values[*i] >= min_strength && values[*i] > 0.)
+		.filter(|i| (*i == 0 || values[i - 1] <= values[*i]) && (i + 1 >= values.len() || values[i + 1] < values[*i]))
+		.collect();
+
+	// Strongest peaks suppress weaker ones in their neighbourhood
+	peaks.sort_by(|a, b| values[*b].partial_cmp(&values[*a]).unwrap_or(Equal));
+	let mut ret: Vec<usize> = Vec::new();
+	for p in peaks {
+		if ret.iter().all(|r| (*r as i64 - p as i64).abs() as usize >= min_distance) {
+			ret.push(p);
+		}
+	}
+	ret.sort();
+
+	ret.into_iter().map(|i| BoundaryCandidate::new((i as f64 / sr) as f32, values[i] as f32)).collect()
+}
+
+fn normalized_columns<R, C, S>(s: &S) -> Vec<Vec<f64>>
+	where R: Dim, C: Dim, S: Storage<f64, R, C>
+{
+	let rows: Vec<Vec<f64>> = s.as_row_slice_iter().map(|r| r.as_iter().cloned().collect()).collect();
+	(0..s.col_count()).map(|c| {
+		let col: Vec<f64> = rows.iter().map(|r| r[c]).collect();
+		let norm = col.iter().map(|v| v * v).sum::<f64>().sqrt();
+		if norm > 0. { col.iter().map(|v| v / norm).collect() } else { col }
+	}).collect()
+}
+
+/// Moves the boundaries between sections to the strongest candidate within max_distance seconds.
+/// Candidates which would leave either adjacent section shorter than min_section_length seconds are skipped.
+pub fn snap_section_boundaries(sections: &mut Vec<TempoSection>, candidates: &Vec<BoundaryCandidate>, max_distance: f32, min_section_length: f32) {
+	for i in 1..sections.len() {
+		let boundary = sections[i].start();
+		let (lower, upper) = (sections[i - 1].start(), sections[i].end());
+		let candidate = candidates.iter()
+			.filter(|c| (c.time - boundary).abs() <= max_distance && c.time > lower && c.time < upper)
+			.filter(|c| c.time - lower >= min_section_length && upper - c.time >= min_section_length)
+			.max_by(|a, b| a.strength.partial_cmp(&b.strength).unwrap_or(Equal));
+
+		if let Some(c) = candidate {
+			sections[i - 1].set_end(c.time);
+			sections[i].set_start(c.time);
+		}
+	}
+}
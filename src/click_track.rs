@@ -5,38 +5,275 @@ use crate::TempoSection;
 use std::path::Path;
 use litaudioio::error::Error;
 
-pub fn save_tempo_click_track<C, L, P, S>(path: &Path, a: S, sections: &Vec<TempoSection>, click_fraction: u32) -> Result<(), Error>
-	where C: Dim, L: Dim, P: SamplePackingType, S: AudioStorageMut<f32, C, L, P>
-{
-	let click_track = click_track(sections, a.sample_rate() as f64, click_fraction);
-	let mut output = a;
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ClickKind {
+	Downbeat,
+	Beat,
+	Subdivision,
+}
+
+#[derive(Debug, Clone)]
+pub enum ClickSource {
+	/// Exponentially decaying sine.
+	Sine { freq: f64, duration: f32 },
+	/// Mono sample with its own sample rate.
+	Sample { data: Vec<f32>, sr: f64 },
+}
+
+#[derive(Debug, Clone)]
+pub struct ClickVoice {
+	source: ClickSource,
+	gain: f32,
+}
+
+impl ClickVoice {
+	pub fn new(source: ClickSource, gain: f32) -> Self { Self { source, gain } }
+
+	pub fn sine(freq: f64, duration: f32, gain: f32) -> Self { Self::new(ClickSource::Sine { freq, duration }, gain) }
+
+	/// Loads the click from an audio file. Multiple channels are mixed down to mono.
+	pub fn from_file(path: &Path, gain: f32) -> Result<Self, Error> {
+		let audio: AudioDeinterleaved<f64, Dynamic, Dynamic> = litaudioio::read_audio(path)?;
+		let channel_count = audio.channel_count().max(1) as f64;
+		let mut data = vec![0.; audio.sample_count()];
+		for ch in audio.as_row_slice_iter() {
+			data.iter_mut().zip(ch.as_iter()).for_each(|(o, i)| *o += (*i / channel_count) as f32);
+		}
+
+		Ok(Self::new(ClickSource::Sample { data, sr: audio.sample_rate() as f64 }, gain))
+	}
 
-	match click_track {
-		Some(click_track) => {
-			for mut ch in output.as_row_slice_mut_iter() {
-				ch.as_iter_mut().zip(click_track.as_iter()).for_each(|(o, c)| *o = clamp(*o + *c, -1., 1.))
+	pub fn source(&self) -> &ClickSource { &self.source }
+
+	pub fn gain(&self) -> f32 { self.gain }
+
+	pub fn set_gain(&mut self, v: f32) { self.gain = v }
+
+	/// Renders the click at given sample rate. Samples are linearly resampled if needed.
+	pub fn render(&self, sr: f64) -> RowVec<f32, Dynamic> {
+		let mut ret = match &self.source {
+			ClickSource::Sine { freq, duration } => click_sound_custom(sr, *duration, *freq),
+			ClickSource::Sample { data, sr: sample_sr } => {
+				let len = (data.len() as f64 * sr / sample_sr).round() as usize;
+				let resampled: Vec<f32> = (0..len).map(|i| {
+					let pos = i as f64 * sample_sr / sr;
+					let (j, t) = (pos.floor() as usize, (pos - pos.floor()) as f32);
+					let a = data.get(j).cloned().unwrap_or(0.);
+					let b = data.get(j + 1).cloned().unwrap_or(0.);
+					a + (b - a) * t
+				}).collect();
+				ContainerRM::from_vec(U1, D!(len), &resampled)
+			},
+		};
+		ret *= self.gain;
+		ret
+	}
+}
+
+#[derive(Debug, Clone, Builder, Getters)]
+pub struct ClickVoices {
+	/// Click at the start of every bar.
+	#[builder(default = "ClickVoice::sine(1500., 0.1, 1.)")]
+	downbeat: ClickVoice,
+	/// Click at every quarter note.
+	#[builder(default = "ClickVoice::sine(1000., 0.1, 0.8)")]
+	beat: ClickVoice,
+	/// Click at every other note subdivision.
+	#[builder(default = "ClickVoice::sine(700., 0.05, 0.4)")]
+	subdivision: ClickVoice,
+}
+
+impl Default for ClickVoices {
+	fn default() -> Self { ClickVoicesBuilder::default().build().unwrap() }
+}
+
+impl ClickVoices {
+	pub fn voice(&self, kind: ClickKind) -> &ClickVoice {
+		match kind {
+			ClickKind::Downbeat => &self.downbeat,
+			ClickKind::Beat => &self.beat,
+			ClickKind::Subdivision => &self.subdivision,
+		}
+	}
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Limiter {
+	/// Clamps the signal to [-1, 1].
+	HardClip,
+	/// Linear up to the threshold after which the signal is smoothly compressed towards 1.
+	Soft { threshold: f32 },
+}
+
+impl Limiter {
+	pub fn apply(&self, v: f32) -> f32 {
+		match *self {
+			Limiter::HardClip => clamp(v, -1., 1.),
+			Limiter::Soft { threshold } => {
+				let threshold = clamp(threshold, 0., 0.999);
+				if v.abs() <= threshold { return v; }
+				let headroom = 1. - threshold;
+				v.signum() * (threshold + headroom * ((v.abs() - threshold) / headroom).tanh())
 			}
-		},
-		None => {}
+		}
 	}
+}
 
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ClickRouting {
+	/// Click mixed into every channel of the music.
+	Mix,
+	/// Music mixed down to mono on the left and click on the right channel.
+	Split,
+	/// Only the click track.
+	ClickOnly,
+}
+
+#[derive(Debug, Clone, Builder, Getters)]
+pub struct ClickTrackOptions {
+	/// Sounds used for downbeats, beats and subdivisions.
+	#[builder(default = "ClickVoices::default()")]
+	voices: ClickVoices,
+	/// Click gain in dB.
+	#[builder(default = "0.")]
+	click_gain: f32,
+	/// Music gain in dB.
+	#[builder(default = "0.")]
+	music_gain: f32,
+	/// Attenuation in dB of the music while a click is sounding. Disabled if None.
+	#[builder(default = "None")]
+	ducking: Option<f32>,
+	/// Release time in seconds of the ducking.
+	#[builder(default = "0.05")]
+	ducking_release: f32,
+	/// Limiter applied to every output channel after mixing.
+	#[builder(default = "Limiter::Soft { threshold: 0.9 }")]
+	limiter: Limiter,
+	/// How music and click are distributed over the output channels.
+	#[builder(default = "ClickRouting::Mix")]
+	routing: ClickRouting,
+}
+
+impl Default for ClickTrackOptions {
+	fn default() -> Self { ClickTrackOptionsBuilder::default().build().unwrap() }
+}
+
+pub fn save_tempo_click_track<C, L, P, S>(path: &Path, a: &S, sections: &Vec<TempoSection>, click_fraction: u32, options: &ClickTrackOptions) -> Result<(), Error>
+	where C: Dim, L: Dim, P: SamplePackingType, S: AudioStorage<f32, C, L, P>
+{
+	let output = render_tempo_click_track(a, sections, click_fraction, options);
 	litaudioio::write_audio(path, &output)
 }
 
+/// Mixes the click track of the sections with the music according to the options.
+pub fn render_tempo_click_track<C, L, P, S>(a: &S, sections: &Vec<TempoSection>, click_fraction: u32, options: &ClickTrackOptions)
+	-> AudioDeinterleaved<f32, Dynamic, Dynamic>
+	where C: Dim, L: Dim, P: SamplePackingType, S: AudioStorage<f32, C, L, P>
+{
+	let sr = a.sample_rate() as f64;
+	let len = a.sample_count();
 
-pub fn click_track(sections: &Vec<TempoSection>, sr: f64, click_fraction: u32) -> Option<AudioDeinterleaved<f32, U1, Dynamic>> {
-	if sections.is_empty() { return None; }
-	let len = ((sections.last().unwrap().end() - sections.first().unwrap().start()) as f64 * sr).round() as usize;
-	let mut ret = AudioDeinterleaved::new(DeinterleavedStorage::zeros(U1,  D!(len)), sr as i32);
-
-	for s in sections {
-		let clicks = click_track_from_section(s, sr, click_fraction);
-		let start = (s.start() as f64 * sr).round() as usize;
-		let end = (start + clicks.sample_count()).min(ret.sample_count());
-		ret.slice_samples_mut(start..end).copy_from(&clicks.slice_samples(0..(end - start)))
+	let click_gain = db_to_gain(options.click_gain);
+	let mut click = vec![0.; len];
+	if let Some(click_track) = click_track(sections, sr, click_fraction, &options.voices) {
+		click.iter_mut().zip(click_track.as_iter()).for_each(|(o, c)| *o = *c * click_gain);
+	}
+
+	let music_gain = db_to_gain(options.music_gain);
+	let ducking = options.ducking.map(|d| ducking_envelope(&click, sr, d, options.ducking_release));
+	let music: Vec<Vec<f32>> = a.as_row_slice_iter().map(|ch| {
+		ch.as_iter().enumerate()
+			.map(|(i, v)| *v * music_gain * ducking.as_ref().map(|d| d[i]).unwrap_or(1.))
+			.collect()
+	}).collect();
+
+	let channels: Vec<Vec<f32>> = match options.routing {
+		ClickRouting::Mix => music.iter()
+			.map(|ch| ch.iter().zip(click.iter()).map(|(m, c)| m + c).collect())
+			.collect(),
+		ClickRouting::Split => {
+			let channel_count = music.len().max(1) as f32;
+			let mono = (0..len).map(|i| music.iter().map(|ch| ch[i]).sum::<f32>() / channel_count).collect();
+			vec![mono, click]
+		},
+		ClickRouting::ClickOnly => vec![click],
+	};
+
+	let mut ret = AudioDeinterleaved::new(DeinterleavedStorage::zeros(D!(channels.len()), D!(len)), a.sample_rate());
+	for (mut ch, data) in ret.as_row_slice_mut_iter().zip(channels.iter()) {
+		ch.as_iter_mut().zip(data.iter()).for_each(|(o, v)| *o = options.limiter.apply(*v));
 	}
 
-	Some(ret)
+	ret
+}
+
+pub fn db_to_gain(db: f32) -> f32 { 10f32.powf(db / 20.) }
+
+/// Gain per sample which attenuates the music by given dB while the click sounds and recovers afterwards.
+fn ducking_envelope(click: &Vec<f32>, sr: f64, attenuation: f32, release: f32) -> Vec<f32> {
+	let depth = 1. - db_to_gain(-attenuation.abs());
+	let release = (-1. / (release.max(0.001) as f64 * sr)).exp() as f32;
+	let mut envelope = 0.;
+
+	click.iter().map(|c| {
+		envelope = if c.abs() > 1e-4 { 1. } else { envelope * release };
+		1. - depth * envelope
+	}).collect()
+}
+
+
+/// Click track in absolute time starting at 0 and lasting until the end of the last section.
+pub fn click_track(sections: &Vec<TempoSection>, sr: f64, click_fraction: u32, voices: &ClickVoices) -> Option<AudioDeinterleaved<f32, U1, Dynamic>> {
+	if sections.is_empty() { return None; }
+	let len = (sections.iter().map(|s| s.end()).fold(0., f32::max) as f64 * sr).round() as usize;
+	let positions = click_positions(sections, click_fraction);
+
+	Some(click_track_from_accented_positions(&positions, sr, D!(len), voices))
+}
+
+/// Absolute click times of all sections. See `click_positions_from_section`.
+pub fn click_positions(sections: &Vec<TempoSection>, click_fraction: u32) -> Vec<(f32, ClickKind)> {
+	click_positions_custom(sections, click_fraction, (4, 4))
+}
+
+/// Absolute click times of all sections accented according to the time signature (numerator, denominator).
+pub fn click_positions_custom(sections: &Vec<TempoSection>, click_fraction: u32, time_signature: (u8, u8)) -> Vec<(f32, ClickKind)> {
+	sections.iter().flat_map(|s| click_positions_from_section_custom(s, click_fraction, time_signature)).collect()
+}
+
+/// Absolute quarter note times of all sections.
+pub fn beat_times(sections: &Vec<TempoSection>) -> Vec<f32> {
+	click_positions(sections, 4).into_iter().map(|(position, _)| position).collect()
+}
+
+/// Absolute click times of a continuous grid through the section offset clipped to [start, end) of the section.
+pub fn click_positions_from_section(s: &TempoSection, click_fraction: u32) -> Vec<(f32, ClickKind)> {
+	click_positions_from_section_custom(s, click_fraction, (4, 4))
+}
+
+pub fn click_positions_from_section_custom(s: &TempoSection, click_fraction: u32, time_signature: (u8, u8)) -> Vec<(f32, ClickKind)> {
+	click_grid_custom(s.bpm(), s.offset(), s.start(), s.end(), click_fraction, time_signature)
+}
+
+/// Clicks every 1/note_fraction of a 4/4 bar aligned at offset within [start, end). Times are absolute.
+pub fn click_grid(bpm: f32, offset: f32, start: f32, end: f32, note_fraction: u32) -> Vec<(f32, ClickKind)> {
+	click_grid_custom(bpm, offset, start, end, note_fraction, (4, 4))
+}
+
+/// Clicks every 1/note_fraction note with the first downbeat at offset. Bars follow the time signature.
+pub fn click_grid_custom(bpm: f32, offset: f32, start: f32, end: f32, note_fraction: u32, time_signature: (u8, u8)) -> Vec<(f32, ClickKind)> {
+	if bpm <= 0. || note_fraction == 0 || end <= start { return Vec::new(); }
+	let bar_len = 60. / bpm as f64 * 4.;
+	let frac_note_len = bar_len / note_fraction as f64;
+
+	// Computed from the grid index to not accumulate rounding errors
+	let first = ((start as f64 - offset as f64) / frac_note_len).ceil() as i64;
+	(first..)
+		.map(|i| (offset as f64 + i as f64 * frac_note_len, i))
+		.skip_while(|(position, _)| *position < start as f64)
+		.take_while(|(position, _)| *position < end as f64)
+		.map(|(position, i)| (position as f32, click_kind_custom(i, note_fraction, time_signature)))
+		.collect()
 }
 
 pub fn click_sound_custom(sr: f64, duration: f32, freq: f64) -> RowVec<f32, Dynamic> {
@@ -49,23 +286,40 @@ pub fn click_sound_custom(sr: f64, duration: f32, freq: f64) -> RowVec<f32, Dyna
 
 pub fn click_sound(sr: f64) -> RowVec<f32, Dynamic> { click_sound_custom(sr, 0.1, 1000.) }
 
-pub fn click_track_from_section(s: &TempoSection, sr: f64, note_fraction: u32) -> AudioDeinterleaved<f32, U1, Dynamic> {
-	click_track_from_tempo(s.bpm(), s.offset(), D!((s.duration() as f64 * sr).round() as usize), sr, note_fraction)
+/// Click track of the section only. Starts at the section start.
+pub fn click_track_from_section(s: &TempoSection, sr: f64, note_fraction: u32, voices: &ClickVoices) -> AudioDeinterleaved<f32, U1, Dynamic> {
+	let positions: Vec<_> = click_positions_from_section(s, note_fraction).into_iter()
+		.map(|(position, kind)| (position - s.start(), kind))
+		.collect();
+	click_track_from_accented_positions(&positions, sr, D!((s.duration() as f64 * sr).round() as usize), voices)
 }
 
-pub fn click_track_from_tempo<D: Dim>(bpm: f32, offset: f32, length: D, sr: f64, note_fraction: u32) -> AudioDeinterleaved<f32, U1, D> {
+/// Click track starting at time 0 with the grid aligned at offset.
+pub fn click_track_from_tempo<D: Dim>(bpm: f32, offset: f32, length: D, sr: f64, note_fraction: u32, voices: &ClickVoices) -> AudioDeinterleaved<f32, U1, D> {
 	let end = (length.value() as f64 / sr) as f32;
-	let bar_len = 60. / bpm * 4.;
-	let frac_note_len = bar_len / note_fraction as f32;
-	let mut positions = Vec::new();
-	let mut position = offset;
-
-	while position < end {
-		positions.push(position);
-		position += frac_note_len;
-	}
+	let positions = click_grid(bpm, offset, 0., end, note_fraction);
+	click_track_from_accented_positions(&positions, sr, length, voices)
+}
 
-	click_track_from_positions(&positions, sr, length)
+/// Kind of the i-th click relative to a downbeat when clicking every 1/note_fraction of a 4/4 bar.
+pub fn click_kind(i: i64, note_fraction: u32) -> ClickKind {
+	click_kind_custom(i, note_fraction, (4, 4))
+}
+
+/// Kind of the i-th click relative to a downbeat when clicking every 1/note_fraction note.
+/// Beats are the notes of the time signature's denominator.
+pub fn click_kind_custom(i: i64, note_fraction: u32, time_signature: (u8, u8)) -> ClickKind {
+	let note_fraction = note_fraction.max(1) as i64;
+	let (numerator, denominator) = (time_signature.0.max(1) as i64, time_signature.1.max(1) as i64);
+	// Position in units of 1 / (note_fraction * denominator) whole notes
+	let bar_position = (i * denominator).rem_euclid(numerator * note_fraction);
+	if bar_position == 0 {
+		ClickKind::Downbeat
+	} else if bar_position % note_fraction == 0 {
+		ClickKind::Beat
+	} else {
+		ClickKind::Subdivision
+	}
 }
 
 pub fn click_track_from_positions<D: Dim>(p: &Vec<f32>, sr: f64, length: D) -> AudioDeinterleaved<f32, U1, D>
@@ -83,4 +337,34 @@ pub fn click_track_from_positions<D: Dim>(p: &Vec<f32>, sr: f64, length: D) -> A
 	}
 
 	ret
-}
\ No newline at end of file
+}
+
+/// Renders each click with the voice of its kind. Overlapping clicks are summed.
+pub fn click_track_from_accented_positions<D: Dim>(p: &Vec<(f32, ClickKind)>, sr: f64, length: D, voices: &ClickVoices)
+	-> AudioDeinterleaved<f32, U1, D>
+{
+	let mut ret = AudioDeinterleaved::new(DeinterleavedStorage::zeros(U1, length), sr.round() as i32);
+	let clicks = [
+		voices.downbeat.render(sr),
+		voices.beat.render(sr),
+		voices.subdivision.render(sr),
+	];
+
+	for (pos, kind) in p {
+		if *pos < 0. { continue; }
+		let pos = (*pos as f64 * sr).round() as usize;
+		if pos >= length.value() { continue; }
+
+		let click = match kind {
+			ClickKind::Downbeat => &clicks[0],
+			ClickKind::Beat => &clicks[1],
+			ClickKind::Subdivision => &clicks[2],
+		};
+		let click_len = click.col_count().min(ret.col_count() - pos);
+		ret.slice_samples_mut(pos..pos + click_len).as_iter_mut()
+			.zip(click.slice_cols(0..click_len).as_iter())
+			.for_each(|(o, c)| *o += *c);
+	}
+
+	ret
+}
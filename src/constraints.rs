@@ -0,0 +1,94 @@
+use litcontainers::*;
+use crate::{TempoSection, CyclicTempogramSettings, CyclicTempogramSettingsBuilder};
+
+/// Fields of user supplied sections which are kept as they are.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct LockedFields {
+	pub boundaries: bool,
+	pub bpm: bool,
+	pub offset: bool,
+}
+
+/// Prior knowledge about the tempo which restricts the extraction.
+#[derive(Debug, Clone, Builder, Getters)]
+pub struct TempoConstraints {
+	/// Range the bpm of every section lies in. Narrows the scanned bpms.
+	#[builder(default = "None")]
+	bpm_range: Option<(f32, f32)>,
+	/// Exact bpm of the piece. Only the offset is estimated.
+	#[builder(default = "None")]
+	fixed_bpm: Option<f32>,
+	/// Rough sections which are refined instead of discovered. Sections without a positive bpm get it estimated.
+	#[builder(default = "Vec::new()")]
+	sections: Vec<TempoSection>,
+	/// Fields of the supplied sections which are not refined.
+	#[builder(default = "LockedFields::default()")]
+	locked: LockedFields,
+}
+
+impl Default for TempoConstraints {
+	fn default() -> Self { TempoConstraintsBuilder::default().build().unwrap() }
+}
+
+impl TempoConstraints {
+	pub fn is_empty(&self) -> bool {
+		self.bpm_range.is_none() && self.fixed_bpm.is_none() && self.sections.is_empty()
+	}
+
+	/// Whether the bpm of the sections may not be changed.
+	pub fn bpm_locked(&self) -> bool {
+		self.fixed_bpm.is_some() || (!self.sections.is_empty() && self.locked.bpm)
+	}
+
+	pub fn offset_locked(&self) -> bool { !self.sections.is_empty() && self.locked.offset }
+
+	pub fn boundaries_locked(&self) -> bool { !self.sections.is_empty() && self.locked.boundaries }
+
+	/// Scanned bpms within the bpm range. All bpms are kept if none lie within.
+	pub fn narrow_scan_bpms(&self, scan_bpms: &RowVec<f64, Dynamic>) -> RowVec<f64, Dynamic> {
+		let (min, max) = match self.bpm_range {
+			Some((min, max)) => (min as f64, max as f64),
+			None => return scan_bpms.clone(),
+		};
+		let bpms: Vec<f64> = scan_bpms.as_iter().cloned().filter(|v| *v >= min && *v <= max).collect();
+		if bpms.len() < 2 {
+			tracing::warn!(min, max, "Bpm range contains too few scanned bpms, ignoring it");
+			return scan_bpms.clone();
+		}
+		ContainerRM::from_vec(U1, D!(bpms.len()), &bpms)
+	}
+
+	/// Cyclic tempogram settings folding exactly the octaves of the narrowed bpms if a bpm range is set.
+	/// Otherwise a preset octave range could miss the narrowed bpms and leave the cyclic tempogram empty.
+	pub fn narrow_cyclic_settings(&self, settings: &CyclicTempogramSettings, bpms: &RowVec<f64, Dynamic>, ref_tempo: f64) -> CyclicTempogramSettings {
+		if self.bpm_range.is_none() { return settings.clone(); }
+		let octave_range = crate::octave_range_covering(bpms, ref_tempo);
+		CyclicTempogramSettingsBuilder::default()
+			.interpolation(*settings.interpolation())
+			.octave_range(Some(octave_range))
+			.octave_weights(settings.octave_weights().clone())
+			.pooling(*settings.pooling())
+			.build().unwrap()
+	}
+
+	/// Bpm moved by octaves into the bpm range and clamped if the range is narrower than an octave.
+	pub fn fit_bpm(&self, bpm: f32) -> f32 {
+		match self.bpm_range {
+			Some(range) => fit_bpm_to_range(bpm, range),
+			None => bpm,
+		}
+	}
+}
+
+pub fn fit_bpm_to_range(bpm: f32, (min, max): (f32, f32)) -> f32 {
+	if bpm <= 0. || min <= 0. || max < min { return bpm; }
+	let mut ret = bpm;
+	while ret < min { ret *= 2.; }
+	while ret > max { ret /= 2.; }
+	if ret < min {
+		// Range is narrower than an octave, take the edge closest in octaves
+		if min / ret < ret * 2. / max { min } else { max }
+	} else {
+		ret
+	}
+}
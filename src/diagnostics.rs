@@ -0,0 +1,54 @@
+use litcontainers::*;
+use std::time::Instant;
+use tracing::span::EnteredSpan;
+
+/// Suspicious input which likely leads to unreliable tempo estimates.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum AudioIssue {
+	/// Audio is shorter than the given minimum duration.
+	Short { duration: f64 },
+	/// Peak amplitude is below -60 dBFS.
+	NearSilent { peak: f64 },
+	/// Fraction of samples at full scale.
+	Clipped { fraction: f64 },
+}
+
+pub fn inspect_audio<C, S>(s: &S, sr: f64, min_duration: f64) -> Vec<AudioIssue>
+	where C: Dim, S: Storage<f64, U1, C>
+{
+	let mut ret = Vec::new();
+	let sample_count = s.col_count();
+	let duration = sample_count as f64 / sr;
+	if duration < min_duration {
+		ret.push(AudioIssue::Short { duration });
+	}
+	if sample_count == 0 { return ret; }
+
+	let peak = s.as_iter().fold(0., |acc: f64, v| acc.max(v.abs()));
+	if peak < 0.001 {
+		ret.push(AudioIssue::NearSilent { peak });
+	}
+
+	let clipped = s.as_iter().filter(|v| v.abs() >= 0.999).count();
+	let fraction = clipped as f64 / sample_count as f64;
+	if fraction > 0.001 {
+		ret.push(AudioIssue::Clipped { fraction });
+	}
+	ret
+}
+
+/// Entered span which records its duration when dropped.
+pub(crate) struct TimedSpan {
+	_span: EnteredSpan,
+	start: Instant,
+}
+
+pub(crate) fn timed(span: tracing::Span) -> TimedSpan {
+	TimedSpan { _span: span.entered(), start: Instant::now() }
+}
+
+impl Drop for TimedSpan {
+	fn drop(&mut self) {
+		tracing::debug!(elapsed_ms = self.start.elapsed().as_secs_f64() * 1000., "done");
+	}
+}
@@ -0,0 +1,272 @@
+use crate::{TempoSection, TempoAnnotation};
+use std::cmp::Ordering::Equal;
+
+#[derive(Debug, Clone, Builder, Getters)]
+pub struct EvaluationSettings {
+	/// Relative tolerance for tempo accuracy.
+	#[builder(default = "0.04")]
+	tempo_tolerance: f32,
+	/// Beats before this time in seconds are ignored.
+	#[builder(default = "5.")]
+	min_beat_time: f32,
+	/// Window in seconds in which an estimated beat counts as correct for the f-measure.
+	#[builder(default = "0.07")]
+	f_measure_window: f32,
+	/// Standard deviation in seconds of the gaussian error function of Cemgil's accuracy.
+	#[builder(default = "0.04")]
+	cemgil_sigma: f32,
+	/// Window relative to the median inter beat interval over which P-score correlates.
+	#[builder(default = "0.2")]
+	p_score_threshold: f32,
+	/// Maximum phase error relative to the inter beat interval for continuity based measures.
+	#[builder(default = "0.175")]
+	continuity_phase_threshold: f32,
+	/// Maximum period error relative to the inter beat interval for continuity based measures.
+	#[builder(default = "0.175")]
+	continuity_period_threshold: f32,
+	/// Amount of histogram bins for the information gain.
+	#[builder(default = "41")]
+	information_gain_bins: usize,
+}
+
+impl Default for EvaluationSettings {
+	fn default() -> Self { EvaluationSettingsBuilder::default().build().unwrap() }
+}
+
+#[derive(Debug, Clone, Default, Getters)]
+pub struct TempoScores {
+	/// Fraction of the reference duration where the tempo is within tolerance.
+	accuracy1: f32,
+	/// Same as accuracy1 but also accepts double, triple, half and third tempo.
+	accuracy2: f32,
+}
+
+#[derive(Debug, Clone, Default, Getters)]
+pub struct BeatScores {
+	f_measure: f32,
+	cemgil: f32,
+	p_score: f32,
+	/// Longest continuous correct segment at the correct metrical level.
+	cml_c: f32,
+	/// Total correct beats at the correct metrical level.
+	cml_t: f32,
+	/// Longest continuous correct segment at any allowed metrical level.
+	aml_c: f32,
+	/// Total correct beats at any allowed metrical level.
+	aml_t: f32,
+	/// Information gain in bits, at most log2 of the histogram bins.
+	information_gain: f32,
+}
+
+/// Evaluates tempo and beats of the sections. Beats of the reference are taken from its sections if it has none.
+pub fn evaluate_sections(estimated: &Vec<TempoSection>, reference: &TempoAnnotation, settings: &EvaluationSettings)
+	-> (TempoScores, BeatScores)
+{
+	let reference_beats = if reference.beats().is_empty() { crate::beat_times(reference.sections()) } else { reference.beats().clone() };
+	(
+		evaluate_tempo(estimated, reference.sections(), settings),
+		evaluate_beats(&crate::beat_times(estimated), &reference_beats, settings),
+	)
+}
+
+/// Tempo accuracy weighted by the duration both sections overlap.
+pub fn evaluate_tempo(estimated: &Vec<TempoSection>, reference: &Vec<TempoSection>, settings: &EvaluationSettings) -> TempoScores {
+	let total: f32 = reference.iter().map(|s| s.duration().max(0.)).sum();
+	if total <= 0. { return TempoScores::default(); }
+
+	let (mut accuracy1, mut accuracy2) = (0., 0.);
+	for r in reference {
+		for e in estimated {
+			let overlap = r.end().min(e.end()) - r.start().max(e.start());
+			if overlap <= 0. { continue; }
+			let (a1, a2) = tempo_accuracy(e.bpm(), r.bpm(), settings.tempo_tolerance);
+			if a1 { accuracy1 += overlap; }
+			if a2 { accuracy2 += overlap; }
+		}
+	}
+
+	TempoScores { accuracy1: accuracy1 / total, accuracy2: accuracy2 / total }
+}
+
+/// Whether the tempo is within tolerance of the reference and whether it is with octave equivalence.
+pub fn tempo_accuracy(estimated: f32, reference: f32, tolerance: f32) -> (bool, bool) {
+	let within = |factor: f32| (estimated - reference * factor).abs() <= tolerance * reference * factor;
+	(within(1.), [1., 2., 3., 1. / 2., 1. / 3.].iter().any(|f| within(*f)))
+}
+
+pub fn evaluate_beats(estimated: &Vec<f32>, reference: &Vec<f32>, settings: &EvaluationSettings) -> BeatScores {
+	let estimated = trim_beats(estimated, settings.min_beat_time);
+	let reference = trim_beats(reference, settings.min_beat_time);
+	if estimated.is_empty() || reference.is_empty() { return BeatScores::default(); }
+
+	let (cml_c, cml_t, aml_c, aml_t) = continuity(&estimated, &reference, settings.continuity_phase_threshold, settings.continuity_period_threshold);
+	BeatScores {
+		f_measure: f_measure(&estimated, &reference, settings.f_measure_window),
+		cemgil: cemgil(&estimated, &reference, settings.cemgil_sigma),
+		p_score: p_score(&estimated, &reference, settings.p_score_threshold),
+		cml_c, cml_t, aml_c, aml_t,
+		information_gain: information_gain(&estimated, &reference, settings.information_gain_bins),
+	}
+}
+
+/// Sorted beats at or after min_time.
+pub fn trim_beats(beats: &Vec<f32>, min_time: f32) -> Vec<f32> {
+	let mut ret: Vec<f32> = beats.iter().cloned().filter(|b| *b >= min_time).collect();
+	ret.sort_by(|a, b| a.partial_cmp(b).unwrap_or(Equal));
+	ret
+}
+
+/// Harmonic mean of precision and recall where each reference beat matches at most one estimated beat.
+pub fn f_measure(estimated: &Vec<f32>, reference: &Vec<f32>, window: f32) -> f32 {
+	if estimated.is_empty() || reference.is_empty() { return 0.; }
+
+	let mut used = vec![false; estimated.len()];
+	let mut matches = 0;
+	for r in reference {
+		let candidate = estimated.iter().enumerate()
+			.filter(|(i, e)| !used[*i] && (*e - r).abs() <= window)
+			.min_by(|a, b| (a.1 - r).abs().partial_cmp(&(b.1 - r).abs()).unwrap_or(Equal));
+		if let Some((i, _)) = candidate {
+			used[i] = true;
+			matches += 1;
+		}
+	}
+
+	let precision = matches as f32 / estimated.len() as f32;
+	let recall = matches as f32 / reference.len() as f32;
+	if precision + recall > 0. { 2. * precision * recall / (precision + recall) } else { 0. }
+}
+
+pub fn cemgil(estimated: &Vec<f32>, reference: &Vec<f32>, sigma: f32) -> f32 {
+	if estimated.is_empty() || reference.is_empty() { return 0.; }
+
+	let accuracy: f32 = reference.iter().map(|r| {
+		let error = estimated.iter().map(|e| (e - r).abs()).fold(std::f32::INFINITY, f32::min);
+		(-error * error / (2. * sigma * sigma)).exp()
+	}).sum();
+	accuracy / (0.5 * (estimated.len() + reference.len()) as f32)
+}
+
+/// Amount of beat pairs closer than threshold times the median reference inter beat interval.
+pub fn p_score(estimated: &Vec<f32>, reference: &Vec<f32>, threshold: f32) -> f32 {
+	if estimated.is_empty() || reference.len() < 2 { return 0.; }
+
+	// Quantized to 10ms like the impulse trains of the original definition
+	let quantize = |v: &f32| (v * 100.).round() as i64;
+	let mut intervals: Vec<f32> = reference.windows(2).map(|w| w[1] - w[0]).collect();
+	intervals.sort_by(|a, b| a.partial_cmp(b).unwrap_or(Equal));
+	let window = (threshold * intervals[intervals.len() / 2] * 100.) as i64;
+
+	let correlation = reference.iter().map(quantize)
+		.map(|r| estimated.iter().map(quantize).filter(|e| (e - r).abs() <= window).count())
+		.sum::<usize>();
+	correlation as f32 / estimated.len().max(reference.len()) as f32
+}
+
+/// Continuity based accuracies (CMLc, CMLt, AMLc, AMLt). Allowed metrical levels are
+/// the reference, its off-beats, double tempo and both phases of half tempo.
+pub fn continuity(estimated: &Vec<f32>, reference: &Vec<f32>, phase_threshold: f32, period_threshold: f32) -> (f32, f32, f32, f32) {
+	if estimated.len() < 2 || reference.len() < 2 { return (0., 0., 0., 0.); }
+
+	let double: Vec<f32> = (0..reference.len() * 2 - 1)
+		.map(|i| if i % 2 == 0 { reference[i / 2] } else { (reference[i / 2] + reference[i / 2 + 1]) / 2. })
+		.collect();
+	let variations = vec![
+		reference.clone(),
+		double.iter().skip(1).step_by(2).cloned().collect(),
+		double.clone(),
+		reference.iter().step_by(2).cloned().collect(),
+		reference.iter().skip(1).step_by(2).cloned().collect(),
+	];
+
+	let accuracies: Vec<(f32, f32)> = variations.iter()
+		.map(|v| continuity_accuracy(estimated, v, phase_threshold, period_threshold))
+		.collect();
+	let (cml_c, cml_t) = accuracies[0];
+	let aml_c = accuracies.iter().map(|a| a.0).fold(0., f32::max);
+	let aml_t = accuracies.iter().map(|a| a.1).fold(0., f32::max);
+	(cml_c, cml_t, aml_c, aml_t)
+}
+
+fn continuity_accuracy(estimated: &Vec<f32>, candidates: &Vec<f32>, phase_threshold: f32, period_threshold: f32) -> (f32, f32) {
+	if candidates.len() < 2 { return (0., 0.); }
+
+	let mut used = vec![false; candidates.len()];
+	let mut successes = vec![false; estimated.len()];
+	for (n, e) in estimated.iter().enumerate() {
+		let nearest = nearest_index(candidates, *e);
+		if used[nearest] { continue; }
+
+		// The first beat or annotation looks at the next interval instead of the previous
+		let (reference_interval, estimated_interval) = if nearest == 0 || n == 0 {
+			let reference_interval = if nearest + 1 < candidates.len() { candidates[nearest + 1] - candidates[nearest] } else { candidates[nearest] - candidates[nearest - 1] };
+			let estimated_interval = if n + 1 < estimated.len() { estimated[n + 1] - estimated[n] } else { estimated[n] - estimated[n - 1] };
+			(reference_interval, estimated_interval)
+		} else {
+			(candidates[nearest] - candidates[nearest - 1], estimated[n] - estimated[n - 1])
+		};
+		if reference_interval == 0. { continue; }
+
+		let phase = ((e - candidates[nearest]) / reference_interval).abs();
+		let period = (1. - estimated_interval / reference_interval).abs();
+		if phase < phase_threshold && period < period_threshold {
+			used[nearest] = true;
+			successes[n] = true;
+		}
+	}
+
+	let (mut longest, mut current) = (0, 0);
+	for s in &successes {
+		current = if *s { current + 1 } else { 0 };
+		longest = longest.max(current);
+	}
+	let total = successes.iter().filter(|s| **s).count();
+	(longest as f32 / estimated.len() as f32, total as f32 / estimated.len() as f32)
+}
+
+/// Information gain in bits of the beat error histogram in both directions relative to a uniform histogram
+/// (Davies et al. 2009).
+pub fn information_gain(estimated: &Vec<f32>, reference: &Vec<f32>, bins: usize) -> f32 {
+	if estimated.len() < 2 || reference.len() < 2 || bins < 2 { return 0.; }
+
+	let forward = beat_error_entropy(reference, estimated, bins);
+	let backward = beat_error_entropy(estimated, reference, bins);
+	(bins as f32).log2() - forward.max(backward)
+}
+
+fn beat_error_entropy(reference: &Vec<f32>, estimated: &Vec<f32>, bins: usize) -> f32 {
+	let mut histogram = vec![0.; bins];
+	for e in estimated {
+		let closest = nearest_index(reference, *e);
+		let error = e - reference[closest];
+		let interval = if closest == 0 {
+			reference[1] - reference[0]
+		} else if closest == reference.len() - 1 || error < 0. {
+			reference[closest] - reference[closest - 1]
+		} else {
+			reference[closest + 1] - reference[closest]
+		};
+		if interval <= 0. { continue; }
+
+		// Error relative to the interval wrapped into [-0.5, 0.5)
+		let error = (error / interval + 0.5).rem_euclid(1.) - 0.5;
+		// Bins are centered on multiples of the width, so the first and last bin are half as wide
+		let width = 1. / (bins - 1) as f32;
+		let bin = (((error + 0.5 + width / 2.) / width) as usize).min(bins - 1);
+		histogram[bin] += 1.;
+	}
+
+	// First and last half bin together form the bin around +-0.5
+	histogram[0] += histogram[bins - 1];
+	histogram.pop();
+	let total: f32 = histogram.iter().sum();
+	if total <= 0. { return (bins as f32).log2(); }
+
+	-histogram.iter().map(|h| h / total).filter(|p| *p > 0.).map(|p| p * p.log2()).sum::<f32>()
+}
+
+fn nearest_index(values: &Vec<f32>, v: f32) -> usize {
+	values.iter().enumerate()
+		.min_by(|a, b| (a.1 - v).abs().partial_cmp(&(b.1 - v).abs()).unwrap_or(Equal))
+		.map(|(i, _)| i).unwrap_or(0)
+}
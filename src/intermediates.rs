@@ -0,0 +1,244 @@
+use litcontainers::*;
+use std::path::Path;
+use std::fs::File;
+use std::io::{self, Write};
+use std::fmt::Write as FmtWrite;
+use crate::TempoExtraction;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DumpFormat {
+	/// litio binary container with a json metadata file.
+	Lit,
+	/// NumPy array with a json metadata file.
+	Npy,
+	/// NumPy archive with the array, sample rate, name, axis name and axis.
+	Npz,
+	/// Comma separated values with the metadata as comments.
+	Csv,
+}
+
+enum ArrayData {
+	Real(Vec<f64>),
+	Complex(Vec<c64>),
+}
+
+/// Row major array with the sample rate of its columns and the values of its rows.
+struct Intermediate {
+	name: &'static str,
+	rows: usize,
+	cols: usize,
+	data: ArrayData,
+	sr: f64,
+	axis_name: &'static str,
+	axis: Option<Vec<f64>>,
+	/// Amount of axis values per row. Bands have a low and high edge.
+	axis_width: usize,
+	/// Whether the array is a single row stored as vector.
+	vector: bool,
+}
+
+impl Intermediate {
+	fn real<R: Dim, C: Dim>(name: &'static str, s: &ContainerRM<f64, R, C>, sr: f64) -> Self {
+		Self {
+			name, rows: s.row_count(), cols: s.col_count(), data: ArrayData::Real(s.as_iter().cloned().collect()),
+			sr, axis_name: "", axis: None, axis_width: 1, vector: false,
+		}
+	}
+
+	fn complex<R: Dim, C: Dim>(name: &'static str, s: &ContainerRM<c64, R, C>, sr: f64) -> Self {
+		Self {
+			name, rows: s.row_count(), cols: s.col_count(), data: ArrayData::Complex(s.as_iter().cloned().collect()),
+			sr, axis_name: "", axis: None, axis_width: 1, vector: false,
+		}
+	}
+
+	fn vector(mut self) -> Self {
+		self.vector = true;
+		self
+	}
+
+	fn with_axis<C: Dim>(mut self, axis_name: &'static str, axis: &RowVec<f64, C>) -> Self {
+		self.axis_name = axis_name;
+		self.axis = Some(axis.as_iter().cloned().collect());
+		self
+	}
+
+	fn with_band_axis<R: Dim>(mut self, bands: &ContainerRM<f64, R, U2>) -> Self {
+		self.axis_name = "band_hz";
+		self.axis = Some(bands.as_iter().cloned().collect());
+		self.axis_width = 2;
+		self
+	}
+
+	fn axis_shape(&self) -> Vec<usize> {
+		let len = self.axis.as_ref().map(|a| a.len()).unwrap_or(0);
+		if self.axis_width == 1 { vec![len] } else { vec![len / self.axis_width, self.axis_width] }
+	}
+
+	/// Axis label of the row.
+	fn axis_label(&self, r: usize) -> Option<String> {
+		let axis = self.axis.as_ref()?;
+		let values = &axis[r * self.axis_width..(r + 1) * self.axis_width];
+		Some(values.iter().map(|v| v.to_string()).collect::<Vec<_>>().join("-"))
+	}
+
+	fn shape(&self) -> Vec<usize> {
+		if self.vector { vec![self.cols] } else { vec![self.rows, self.cols] }
+	}
+
+	fn dtype(&self) -> &'static str {
+		match self.data {
+			ArrayData::Real(_) => "float64",
+			ArrayData::Complex(_) => "complex128",
+		}
+	}
+
+	fn metadata(&self) -> serde_json::Value {
+		serde_json::json!({
+			"name": self.name,
+			"dtype": self.dtype(),
+			"shape": self.shape(),
+			"sr": self.sr,
+			"axis_name": self.axis_name,
+			"axis": self.axis.as_ref().map(|axis| {
+				if self.axis_width == 1 { serde_json::json!(axis) } else { serde_json::json!(axis.chunks(self.axis_width).collect::<Vec<_>>()) }
+			}),
+		})
+	}
+}
+
+/// Writes the novelty curve, band odfs, tempograms, cyclic tempogram and tempo curve to the directory.
+/// The tempogram stages are skipped if the extraction had a fixed bpm.
+pub fn save_intermediates(dir: &Path, format: DumpFormat, extraction: &TempoExtraction) -> io::Result<()> {
+	std::fs::create_dir_all(dir)?;
+	let mut intermediates = vec![
+		Intermediate::real("novelty_curve", extraction.novelty_curve(), *extraction.nc_sr()).vector(),
+		Intermediate::real("band_odf", extraction.band_odf(), *extraction.band_odf_sr()).with_band_axis(extraction.bands()),
+	];
+	if let Some(analysis) = extraction.analysis() {
+		intermediates.extend(vec![
+			Intermediate::complex("tempogram", analysis.tempogram(), *analysis.sr()).with_axis("bpm", analysis.bpms()),
+			Intermediate::real("tempogram_mag", analysis.tempogram_mag(), *analysis.sr()).with_axis("bpm", analysis.bpms()),
+			Intermediate::real("cyclic_tempogram", analysis.cyclic_tempogram(), *analysis.sr())
+				.with_axis("cyclic_tempo", analysis.cyclic_tempogram_axis()),
+			Intermediate::real("tempo_curve", analysis.tempo_curve(), *analysis.sr()).vector(),
+		]);
+	}
+
+	for i in &intermediates {
+		match format {
+			DumpFormat::Lit => {
+				let path = dir.join(format!("{}.lit", i.name));
+				let result = match &i.data {
+					ArrayData::Real(data) => litio::write_binary_file(&path, &ContainerRM::from_vec(D!(i.rows), D!(i.cols), data)),
+					ArrayData::Complex(data) => litio::write_binary_file(&path, &ContainerRM::from_vec(D!(i.rows), D!(i.cols), data)),
+				};
+				result.map_err(|e| io::Error::new(io::ErrorKind::Other, format!("{:?}", e)))?;
+				write_metadata(dir, i)?;
+			},
+			DumpFormat::Npy => {
+				File::create(dir.join(format!("{}.npy", i.name)))?.write_all(&npy_bytes(i))?;
+				write_metadata(dir, i)?;
+			},
+			DumpFormat::Npz => {
+				File::create(dir.join(format!("{}.npz", i.name)))?.write_all(&npz_bytes(i)?)?;
+			},
+			DumpFormat::Csv => {
+				File::create(dir.join(format!("{}.csv", i.name)))?.write_all(csv_string(i).as_bytes())?;
+			},
+		}
+	}
+	Ok(())
+}
+
+fn write_metadata(dir: &Path, i: &Intermediate) -> io::Result<()> {
+	File::create(dir.join(format!("{}.json", i.name)))?
+		.write_all(serde_json::to_string_pretty(&i.metadata()).unwrap().as_bytes())
+}
+
+/// NumPy format version 1.0 in little endian and C order.
+fn npy_array(descr: &str, shape: &[usize], data: &[u8]) -> Vec<u8> {
+	let shape = match shape.len() {
+		0 => "()".to_string(),
+		1 => format!("({},)", shape[0]),
+		_ => format!("({})", shape.iter().map(|v| v.to_string()).collect::<Vec<_>>().join(", ")),
+	};
+	let mut header = format!("{{'descr': '{}', 'fortran_order': False, 'shape': {}, }}", descr, shape);
+	// Magic, version and header length take 10 bytes, total header is aligned to 64 bytes
+	let padding = 64 - (10 + header.len() + 1) % 64;
+	header.push_str(&" ".repeat(padding % 64));
+	header.push('\n');
+
+	let mut ret = Vec::with_capacity(10 + header.len() + data.len());
+	ret.extend_from_slice(b"\x93NUMPY\x01\x00");
+	ret.extend_from_slice(&(header.len() as u16).to_le_bytes());
+	ret.extend_from_slice(header.as_bytes());
+	ret.extend_from_slice(data);
+	ret
+}
+
+fn f64_bytes(data: impl Iterator<Item=f64>) -> Vec<u8> {
+	data.flat_map(|v| v.to_le_bytes().to_vec()).collect()
+}
+
+/// Scalar unicode string as NumPy stores it: utf-32 code points.
+fn npy_string(s: &str) -> Vec<u8> {
+	let chars: Vec<char> = s.chars().collect();
+	let data: Vec<u8> = chars.iter().flat_map(|c| (*c as u32).to_le_bytes().to_vec()).collect();
+	npy_array(&format!("<U{}", chars.len().max(1)), &[], &if data.is_empty() { vec![0; 4] } else { data })
+}
+
+fn npy_bytes(i: &Intermediate) -> Vec<u8> {
+	match &i.data {
+		ArrayData::Real(data) => npy_array("<f8", &i.shape(), &f64_bytes(data.iter().cloned())),
+		ArrayData::Complex(data) => npy_array("<c16", &i.shape(), &f64_bytes(data.iter().flat_map(|v| vec![v.re, v.im]))),
+	}
+}
+
+fn npz_bytes(i: &Intermediate) -> io::Result<Vec<u8>> {
+	let mut zip = zip::ZipWriter::new(io::Cursor::new(Vec::new()));
+	let options = zip::write::FileOptions::default().compression_method(zip::CompressionMethod::Stored);
+	let mut add = |name: &str, data: Vec<u8>| -> io::Result<()> {
+		zip.start_file(name, options).map_err(|e| io::Error::new(io::ErrorKind::Other, e.to_string()))?;
+		zip.write_all(&data)
+	};
+	add("data.npy", npy_bytes(i))?;
+	add("sr.npy", npy_array("<f8", &[], &i.sr.to_le_bytes()))?;
+	add("name.npy", npy_string(i.name))?;
+	add("axis_name.npy", npy_string(i.axis_name))?;
+	if let Some(axis) = &i.axis {
+		add("axis.npy", npy_array("<f8", &i.axis_shape(), &f64_bytes(axis.iter().cloned())))?;
+	}
+	let cursor = zip.finish().map_err(|e| io::Error::new(io::ErrorKind::Other, e.to_string()))?;
+	Ok(cursor.into_inner())
+}
+
+/// One line per column with its time. Rows become columns named after their axis value.
+fn csv_string(i: &Intermediate) -> String {
+	let mut ret = String::new();
+	writeln!(ret, "# name={} dtype={} sr={} axis={}", i.name, i.dtype(), i.sr, i.axis_name).unwrap();
+	ret.push_str("time");
+	for r in 0..i.rows {
+		match i.axis_label(r) {
+			Some(label) => write!(ret, ",{}", label).unwrap(),
+			None if i.rows == 1 => ret.push_str(",value"),
+			None => write!(ret, ",{}", r).unwrap(),
+		}
+	}
+	ret.push('\n');
+
+	for c in 0..i.cols {
+		write!(ret, "{:.6}", c as f64 / i.sr).unwrap();
+		for r in 0..i.rows {
+			match &i.data {
+				ArrayData::Real(data) => write!(ret, ",{}", data[r * i.cols + c]).unwrap(),
+				ArrayData::Complex(data) => {
+					let v = data[r * i.cols + c];
+					write!(ret, ",{}{:+}j", v.re, v.im).unwrap()
+				},
+			}
+		}
+		ret.push('\n');
+	}
+	ret
+}
@@ -8,18 +8,44 @@ pub mod tempogram;
 pub mod preprocessing;
 pub mod tempo_curve;
 pub mod tempo_sections;
+pub mod boundary_detection;
 pub mod offset_extraction;
 pub mod click_track;
+pub mod midi_export;
+pub mod marker_export;
+pub mod annotations;
+pub mod evaluation;
+pub mod synthesis;
+pub mod progress;
+pub mod diagnostics;
+pub mod static_plot;
+pub mod terminal_plot;
+pub mod intermediates;
+pub mod constraints;
 pub mod tempo_extraction;
+pub mod presets;
 
 pub use novelty_curve::*;
 pub use tempogram::*;
 pub use preprocessing::*;
 pub use tempo_curve::*;
 pub use tempo_sections::*;
+pub use boundary_detection::*;
 pub use offset_extraction::*;
 pub use click_track::*;
+pub use midi_export::*;
+pub use marker_export::*;
+pub use annotations::*;
+pub use evaluation::*;
+pub use synthesis::*;
+pub use progress::*;
+pub use diagnostics::*;
+pub use static_plot::*;
+pub use terminal_plot::*;
+pub use intermediates::*;
+pub use constraints::*;
 pub use tempo_extraction::*;
+pub use presets::*;
 
 #[cfg(test)]
 mod tests {
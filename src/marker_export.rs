@@ -0,0 +1,151 @@
+use crate::TempoSection;
+use std::path::Path;
+use std::fs::File;
+use std::io::Write;
+use std::fmt::Write as FmtWrite;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MarkerFormat {
+	/// Audacity label track (start, end and label separated by tabs).
+	Audacity,
+	/// REAPER project with tempo envelope and markers.
+	Reaper,
+	/// Sonic Visualiser annotation layer (csv).
+	SonicVisualiser,
+	/// Comma separated values with header.
+	Csv,
+	/// Tab separated values with header.
+	Tsv,
+	/// Json object with the global bpm and all sections or beats.
+	Json,
+	/// osu! beatmap timing points (or hit objects for beats).
+	Osu,
+}
+
+pub fn save_section_markers(path: &Path, sections: &Vec<TempoSection>, format: MarkerFormat) -> std::io::Result<()> {
+	File::create(path)?.write_all(sections_to_markers(sections, format).as_bytes())
+}
+
+pub fn save_beat_markers(path: &Path, beats: &Vec<f32>, format: MarkerFormat) -> std::io::Result<()> {
+	File::create(path)?.write_all(beats_to_markers(beats, format).as_bytes())
+}
+
+pub fn sections_to_markers(sections: &Vec<TempoSection>, format: MarkerFormat) -> String {
+	let mut ret = String::new();
+	match format {
+		MarkerFormat::Audacity => {
+			for s in sections {
+				writeln!(ret, "{:.6}\t{:.6}\t{}", s.start(), s.end(), section_label(s)).unwrap();
+			}
+		},
+		MarkerFormat::Reaper => {
+			let bpm = sections.first().map(|s| s.bpm()).unwrap_or(120.);
+			let markers: Vec<_> = sections.iter().map(|s| (s.start(), section_label(s))).collect();
+			let tempo_points: Vec<_> = sections.iter().enumerate()
+				.map(|(i, s)| (if i == 0 { 0. } else { s.start() }, s.bpm()))
+				.collect();
+			ret = reaper_project(bpm, &tempo_points, &markers);
+		},
+		MarkerFormat::SonicVisualiser => {
+			// Region layer: time, value, duration, label
+			for s in sections {
+				writeln!(ret, "{:.6},{:.3},{:.6},\"{}\"", s.start(), s.bpm(), s.duration(), section_label(s)).unwrap();
+			}
+		},
+		MarkerFormat::Csv | MarkerFormat::Tsv => {
+			let d = delimiter(format);
+			writeln!(ret, "start{}end{}bpm{}offset", d, d, d).unwrap();
+			for s in sections {
+				writeln!(ret, "{:.6}{}{:.6}{}{:.3}{}{:.6}", s.start(), d, s.end(), d, s.bpm(), d, s.offset()).unwrap();
+			}
+		},
+		MarkerFormat::Json => {
+			let entries: Vec<_> = sections.iter().map(|s| serde_json::json!({
+				"start": s.start(),
+				"end": s.end(),
+				"bpm": s.bpm(),
+				"offset": s.offset(),
+			})).collect();
+			let data = serde_json::json!({ "global_bpm": crate::global_bpm(sections), "sections": entries });
+			ret = serde_json::to_string_pretty(&data).unwrap();
+			ret.push('\n');
+		},
+		MarkerFormat::Osu => {
+			writeln!(ret, "[TimingPoints]").unwrap();
+			for s in sections {
+				// Timing points start at the first downbeat within the section
+				let bar_len = 60. / s.bpm() as f64 * 4.;
+				let bar = ((s.start() - s.offset()) as f64 / bar_len).ceil();
+				let time = s.offset() as f64 + bar * bar_len;
+				writeln!(ret, "{},{},4,1,0,100,1,0", (time * 1000.).round() as i64, 60000. / s.bpm() as f64).unwrap();
+			}
+		},
+	}
+	ret
+}
+
+pub fn beats_to_markers(beats: &Vec<f32>, format: MarkerFormat) -> String {
+	let mut ret = String::new();
+	match format {
+		MarkerFormat::Audacity => {
+			for (i, b) in beats.iter().enumerate() {
+				writeln!(ret, "{:.6}\t{:.6}\t{}", b, b, i + 1).unwrap();
+			}
+		},
+		MarkerFormat::Reaper => {
+			let markers: Vec<_> = beats.iter().enumerate().map(|(i, b)| (*b, (i + 1).to_string())).collect();
+			ret = reaper_project(120., &Vec::new(), &markers);
+		},
+		MarkerFormat::SonicVisualiser => {
+			// Time instants layer: time, label
+			for (i, b) in beats.iter().enumerate() {
+				writeln!(ret, "{:.6},\"{}\"", b, i + 1).unwrap();
+			}
+		},
+		MarkerFormat::Csv | MarkerFormat::Tsv => {
+			writeln!(ret, "time").unwrap();
+			for b in beats {
+				writeln!(ret, "{:.6}", b).unwrap();
+			}
+		},
+		MarkerFormat::Json => {
+			ret = serde_json::to_string_pretty(&serde_json::json!({ "beats": beats })).unwrap();
+			ret.push('\n');
+		},
+		MarkerFormat::Osu => {
+			// Hit circles in the center of the playfield
+			writeln!(ret, "[HitObjects]").unwrap();
+			for b in beats {
+				writeln!(ret, "256,192,{},1,0,0:0:0:0:", (*b as f64 * 1000.).round() as i64).unwrap();
+			}
+		},
+	}
+	ret
+}
+
+fn section_label(s: &TempoSection) -> String {
+	format!("{:.2} BPM (offset {:.3})", s.bpm(), s.offset())
+}
+
+fn delimiter(format: MarkerFormat) -> char {
+	if format == MarkerFormat::Tsv { '\t' } else { ',' }
+}
+
+fn reaper_project(bpm: f32, tempo_points: &Vec<(f32, f32)>, markers: &Vec<(f32, String)>) -> String {
+	let mut ret = String::new();
+	writeln!(ret, "<REAPER_PROJECT 0.1 \"5.0\" 0").unwrap();
+	writeln!(ret, "  TEMPO {:.6} 4 4", bpm).unwrap();
+	if !tempo_points.is_empty() {
+		writeln!(ret, "  <TEMPOENVEX").unwrap();
+		for (time, bpm) in tempo_points {
+			// Shape 1 is a square (instant) tempo change
+			writeln!(ret, "    PT {:.6} {:.6} 1", time, bpm).unwrap();
+		}
+		writeln!(ret, "  >").unwrap();
+	}
+	for (i, (time, label)) in markers.iter().enumerate() {
+		writeln!(ret, "  MARKER {} {:.6} \"{}\" 0", i + 1, time, label.replace('"', "'")).unwrap();
+	}
+	writeln!(ret, ">").unwrap();
+	ret
+}
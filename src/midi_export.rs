@@ -0,0 +1,159 @@
+use crate::{TempoSection, ClickKind};
+use std::path::Path;
+use std::fs::File;
+use std::io::Write;
+
+#[derive(Debug, Clone, Builder, Getters)]
+pub struct MidiExportSettings {
+	/// Resolution of the midi file.
+	#[builder(default = "480")]
+	ticks_per_quarter: u16,
+	/// Time signature (numerator, denominator) starting at the first downbeat and used to accent the clicks.
+	#[builder(default = "(4, 4)")]
+	time_signature: (u8, u8),
+	/// Click every xth note in the percussion track. No percussion track is written if None.
+	#[builder(default = "Some(4)")]
+	click_fraction: Option<u32>,
+	/// Percussion notes (General MIDI) for downbeats, beats and subdivisions.
+	#[builder(default = "(76, 77, 42)")]
+	click_notes: (u8, u8, u8),
+}
+
+impl Default for MidiExportSettings {
+	fn default() -> Self { MidiExportSettingsBuilder::default().build().unwrap() }
+}
+
+const PERCUSSION_CHANNEL: u8 = 9;
+
+/// Writes the tempo map and optionally the click track of the sections as a standard midi file (format 1).
+pub fn save_tempo_midi(path: &Path, sections: &Vec<TempoSection>, settings: &MidiExportSettings) -> std::io::Result<()> {
+	File::create(path)?.write_all(&tempo_sections_to_midi(sections, settings))
+}
+
+/// The first downbeat starts a bar. Beats before it form a partial bar with its own tempo.
+pub fn tempo_sections_to_midi(sections: &Vec<TempoSection>, settings: &MidiExportSettings) -> Vec<u8> {
+	let ppq = settings.ticks_per_quarter;
+	let tempo_map = TempoMap::new(sections, ppq, settings.time_signature);
+
+	// Tempo track
+	let (numerator, denominator) = settings.time_signature;
+	let denominator_pow = (denominator.max(1) as f32).log2().round() as u8;
+	let mut tempo_events = Vec::new();
+	for (time, bpm) in &tempo_map.changes {
+		let tick = tempo_map.ticks(*time);
+		let mpq = (60_000_000. / *bpm as f64).round() as u32;
+		tempo_events.push((tick, vec![0xFF, 0x51, 0x03, (mpq >> 16) as u8, (mpq >> 8) as u8, mpq as u8]));
+	}
+	let partial_bar = tempo_map.pickup_beats % numerator.max(1) as u32;
+	if partial_bar > 0 {
+		tempo_events.push((0, vec![0xFF, 0x58, 0x04, partial_bar as u8, denominator_pow, 24, 8]));
+	}
+	tempo_events.push((tempo_map.ticks(tempo_map.downbeat), vec![0xFF, 0x58, 0x04, numerator, denominator_pow, 24, 8]));
+
+	let mut tracks = vec![encode_track(tempo_events)];
+
+	// Percussion track
+	if let Some(click_fraction) = settings.click_fraction {
+		let (downbeat, beat, subdivision) = settings.click_notes;
+		let note_length = (ppq as u32 / 8).max(1);
+		let mut events = Vec::new();
+		for (time, kind) in crate::click_positions_custom(sections, click_fraction, settings.time_signature) {
+			let (note, velocity) = match kind {
+				ClickKind::Downbeat => (downbeat, 127),
+				ClickKind::Beat => (beat, 100),
+				ClickKind::Subdivision => (subdivision, 70),
+			};
+			let tick = tempo_map.ticks(time);
+			events.push((tick, vec![0x90 | PERCUSSION_CHANNEL, note, velocity]));
+			events.push((tick + note_length, vec![0x80 | PERCUSSION_CHANNEL, note, 0]));
+		}
+		// Note offs go before note ons at the same tick
+		events.sort_by_key(|(tick, e)| (*tick, e[0] & 0xF0 == 0x90));
+		tracks.push(encode_track(events));
+	}
+
+	let mut ret = Vec::new();
+	ret.extend_from_slice(b"MThd");
+	ret.extend_from_slice(&6u32.to_be_bytes());
+	ret.extend_from_slice(&1u16.to_be_bytes());
+	ret.extend_from_slice(&(tracks.len() as u16).to_be_bytes());
+	ret.extend_from_slice(&ppq.to_be_bytes());
+	for track in tracks {
+		ret.extend_from_slice(b"MTrk");
+		ret.extend_from_slice(&(track.len() as u32).to_be_bytes());
+		ret.extend(track);
+	}
+	ret
+}
+
+/// Tempo changes at section starts used to convert seconds to ticks.
+struct TempoMap {
+	changes: Vec<(f32, f32)>,
+	ppq: u16,
+	/// Time of the first downbeat of the first section.
+	downbeat: f32,
+	/// Beats (of the time signature denominator) before the first downbeat.
+	pickup_beats: u32,
+}
+
+impl TempoMap {
+	fn new(sections: &Vec<TempoSection>, ppq: u16, time_signature: (u8, u8)) -> Self {
+		let sections: Vec<&TempoSection> = sections.iter().filter(|s| s.bpm() > 0.).collect();
+		let first = match sections.first() {
+			Some(first) => *first,
+			None => return Self { changes: vec![(0., 120.)], ppq, downbeat: 0., pickup_beats: 0 },
+		};
+		let downbeat = crate::click_positions_from_section_custom(first, 4, time_signature).into_iter()
+			.find(|(_, kind)| *kind == ClickKind::Downbeat)
+			.map(|(time, _)| time)
+			.unwrap_or(0.);
+
+		let mut changes = Vec::new();
+		let mut pickup_beats = 0;
+		if downbeat > 0. {
+			// Whole beats fill the lead-in so the first downbeat lands on a bar line
+			let beat_length = 60. / first.bpm() * 4. / time_signature.1.max(1) as f32;
+			pickup_beats = ((downbeat / beat_length).round() as u32).max(1);
+			changes.push((0., first.bpm() * pickup_beats as f32 * beat_length / downbeat));
+		}
+		changes.push((downbeat, first.bpm()));
+		for s in sections.iter().skip(1).filter(|s| s.start() > downbeat) {
+			changes.push((s.start(), s.bpm()));
+		}
+		Self { changes, ppq, downbeat, pickup_beats }
+	}
+
+	fn ticks(&self, time: f32) -> u32 {
+		let mut ret = 0.;
+		for (i, (start, bpm)) in self.changes.iter().enumerate() {
+			if time <= *start { break; }
+			let end = self.changes.get(i + 1).map(|c| c.0.min(time)).unwrap_or(time);
+			ret += (end - start) as f64 * *bpm as f64 / 60. * self.ppq as f64;
+		}
+		ret.round() as u32
+	}
+}
+
+fn encode_track(mut events: Vec<(u32, Vec<u8>)>) -> Vec<u8> {
+	events.sort_by_key(|(tick, _)| *tick);
+	let mut ret = Vec::new();
+	let mut last_tick = 0;
+	for (tick, data) in events {
+		write_vlq(&mut ret, tick - last_tick);
+		ret.extend(data);
+		last_tick = tick;
+	}
+	write_vlq(&mut ret, 0);
+	ret.extend_from_slice(&[0xFF, 0x2F, 0x00]);
+	ret
+}
+
+fn write_vlq(out: &mut Vec<u8>, mut v: u32) {
+	let mut bytes = vec![(v & 0x7F) as u8];
+	v >>= 7;
+	while v > 0 {
+		bytes.push((v & 0x7F) as u8 | 0x80);
+		v >>= 7;
+	}
+	out.extend(bytes.into_iter().rev());
+}
@@ -30,6 +30,20 @@ pub fn calculate_novelty_curve<C, S, W, H, B>(s: &S, sr: f64, window_dim: W, hop
 	      H: Dim,
 	      B: Dim
 {
+	let (novelty_curve, sr, _, _) = calculate_novelty_curve_with_bands(s, sr, window_dim, hop_dim, bands, settings);
+	(novelty_curve, sr)
+}
+
+/// Novelty curve with its sample rate, followed by the band odf it is calculated from and its sample rate.
+pub fn calculate_novelty_curve_with_bands<C, S, W, H, B>(s: &S, sr: f64, window_dim: W, hop_dim: H, bands: &ContainerRM<f64, B, U2>, settings: NCSettings)
+	-> (RowVec<f64, Dynamic>, f64, ContainerRM<f64, B, Dynamic>, f64)
+	where C: Dim, S: Storage<f64, U1, C>,
+	      W: Dim + DimDiv<U2>,
+	      <W as DimDiv<U2>>::Output: DimAdd<U1>,
+	      H: Dim,
+	      B: Dim
+{
+	let _span = crate::diagnostics::timed(tracing::debug_span!("calculate_novelty_curve", sr, samples = s.col_count(), window = window_dim.value(), hop = hop_dim.value()));
 	let (bands_novelty_curve, stft_sr) = calculate_band_odf(s, sr, window_dim, hop_dim, bands, settings.clone());
 
 	let mut sr = stft_sr;
@@ -45,7 +59,7 @@ pub fn calculate_novelty_curve<C, S, W, H, B>(s: &S, sr: f64, window_dim: W, hop
 
 	let novelty_curve = smooth_filter_subtract(&novelty_curve, stft_sr, settings.smooth_length);
 
-	(novelty_curve, sr)
+	(novelty_curve, sr, bands_novelty_curve, stft_sr)
 }
 
 pub fn calculate_band_odf<C, S, W, H, B>(s: &S, sr: f64, window_dim: W, hop_dim: H, bands: &ContainerRM<f64, B, U2>, settings: NCSettings)
@@ -161,6 +175,47 @@ pub fn smooth_filter_subtract<C, S>(s: &S, sr: f64, smooth_length: f64)
 	local_avg
 }
 
+/// Novelty curve from onset times in seconds weighted by their velocities (1 if None).
+/// Every onset is spread with a 50ms hann window. Length defaults to one second after the last onset.
+pub fn onsets_to_novelty_curve(onsets: &[f32], velocities: Option<&[f32]>, sr: f64, length: Option<f32>) -> RowVec<f64, Dynamic> {
+	let length = length.unwrap_or_else(|| onsets.iter().cloned().fold(0., f32::max) + 1.);
+	let n = (length as f64 * sr).ceil().max(1.) as usize;
+	let half = (0.025 * sr).round() as i64;
+	// Hann window peaking at the onset
+	let kernel: Vec<f64> = (-half..=half)
+		.map(|k| 0.5 * (1. + (std::f64::consts::PI * k as f64 / (half + 1) as f64).cos()))
+		.collect();
+
+	let mut ret = ContainerRM::zeros(U1, D!(n));
+	for (i, onset) in onsets.iter().enumerate() {
+		let velocity = velocities.and_then(|v| v.get(i)).cloned().unwrap_or(1.) as f64;
+		let center = (*onset as f64 * sr).round() as i64;
+		for (k, w) in kernel.iter().enumerate() {
+			let pos = center + k as i64 - half;
+			if pos >= 0 && (pos as usize) < n {
+				ret[pos as usize] += velocity * w;
+			}
+		}
+	}
+	ret
+}
+
+/// Novelty curve and its sample rate from evenly spaced (time, value) pairs, e.g. onset strengths of other tools.
+/// The curve is padded with zeros up to the first time so sample positions match the times.
+pub fn timed_novelty_curve(times: &[f64], values: &[f64]) -> Result<(RowVec<f64, Dynamic>, f64), String> {
+	if times.len() < 2 || times.len() != values.len() { return Err("Novelty curve needs at least two time,value rows".to_string()); }
+	if times[0] < 0. { return Err(format!("Novelty curve starts at negative time {}", times[0])); }
+	if let Some(w) = times.windows(2).find(|w| w[1] <= w[0]) {
+		return Err(format!("Novelty curve times must be increasing but {} follows {}", w[1], w[0]));
+	}
+
+	let sr = (times.len() - 1) as f64 / (times[times.len() - 1] - times[0]);
+	let padding = (times[0] * sr).round() as usize;
+	let mut data = vec![0.; padding];
+	data.extend_from_slice(values);
+	Ok((ContainerRM::from_vec(U1, D!(data.len()), &data), sr))
+}
+
 pub fn default_audio_bands(sr: f64) -> ContainerRM<f64, U5, U2>
 {
 	ContainerRM::from_vec(U5, U2, &[
@@ -1,53 +1,91 @@
 use litcontainers::*;
-use crate::TempoSection;
+use crate::{TempoSection, ProgressObserver, Cancelled, NoProgress};
 use std::cmp::Ordering::Equal;
 use rayon::prelude::*;
 
+/// Pulse alignment scores searched by extract_offset.
+#[derive(Debug, Clone, Getters)]
+pub struct OffsetLandscape {
+	/// Candidate bpms (rows).
+	bpms: RowVec<f64, Dynamic>,
+	/// Shifts in seconds (columns). The offset of a shift is the section start minus the shift.
+	shifts: RowVec<f64, Dynamic>,
+	/// Score of every candidate bpm and shift. Shifts beyond a bar of the bpm are zero.
+	scores: ContainerRM<f64, Dynamic, Dynamic>,
+}
+
+/// Sets the offset and bpm of the section to the best aligned pulse and returns all scores.
 pub fn extract_offset<C, S>(nc: &S, sr: f64, s: &mut TempoSection, tempo_multiples: &Vec<f32>, doubt_window: f32, doubt_step: f32)
+	-> OffsetLandscape
 	where C: Dim, S: RowVecStorage<f64, C>
 {
+	extract_offset_custom(nc, sr, s, tempo_multiples, doubt_window, doubt_step, &NoProgress).unwrap()
+}
+
+/// Same as extract_offset but checks for cancellation before scoring every candidate bpm.
+/// The section is left untouched when cancelled.
+pub fn extract_offset_custom<C, S>(nc: &S, sr: f64, s: &mut TempoSection, tempo_multiples: &Vec<f32>, doubt_window: f32, doubt_step: f32, observer: &dyn ProgressObserver)
+	-> Result<OffsetLandscape, Cancelled>
+	where C: Dim, S: RowVecStorage<f64, C>
+{
+	let _span = crate::diagnostics::timed(tracing::debug_span!("extract_offset", start = s.start(), end = s.end(), bpm = s.bpm()));
 	let start = (s.start() as f64 * sr) as usize;
 	let end = ((s.end() as f64 * sr) as usize).min(nc.size());
 	let section_length = end - start;
 
 	let min_bpm = s.bpm() - doubt_window / 2.;
-	let step_count = (doubt_window / doubt_step) as usize;
+	let step_count = ((doubt_window / doubt_step) as usize).max(1);
 	let bpms: Vec<_> = (0..step_count).map(|i| min_bpm + i as f32 * doubt_step).collect();
 
-	let candidates: Vec<_> = bpms.par_iter().cloned().map(|bpm| {
+	let scores: Vec<Vec<f64>> = bpms.par_iter().cloned().map(|bpm| {
+		crate::check_cancelled(observer)?;
 		let samples_per_bar = ((60. / bpm as f64 * sr) * 4.).ceil() as usize;
 		let pulse_dim = D!(section_length + samples_per_bar);
 		let pulses: Vec<_> = tempo_multiples.iter().cloned().map(|m| {
 			litdsp::wave::generate_wave(60. / (bpm * m) as f64, pulse_dim, 0, sr, false)
 		}).collect();
 
-		let (mut magnitude, mut offset) = (0., 0.);
-
 		let roi = nc.slice_cols(start..end);
-		for i in 0..samples_per_bar {
-			let c_magnitude: f64 = pulses.iter().map(|p| {
+		Ok((0..samples_per_bar).map(|i| {
+			pulses.iter().map(|p| {
 				roi.as_iter().zip(p.slice_cols(i..i+section_length).as_iter())
 					.map(|(a, b)| (a * b).max(0.)).sum::<f64>()
-			}).sum();
+			}).sum()
+		}).collect())
+	}).collect::<Result<_, Cancelled>>()?;
 
-			if c_magnitude > magnitude {
-				magnitude = c_magnitude;
+	let candidate = bpms.iter().zip(scores.iter()).map(|(bpm, bpm_scores)| {
+		let (mut magnitude, mut offset) = (0., 0.);
+		for (i, c_magnitude) in bpm_scores.iter().enumerate() {
+			if *c_magnitude > magnitude {
+				magnitude = *c_magnitude;
 				offset = (start as f64 + -(i as f64)) / sr;
 			}
 		}
-
-		(magnitude, offset, bpm)
-	}).collect();
-
-	let candidate = candidates.into_iter().max_by(|a, b| a.0.partial_cmp(&b.0).unwrap_or(Equal));
+		(magnitude, offset, *bpm)
+	}).max_by(|a, b| a.0.partial_cmp(&b.0).unwrap_or(Equal));
 
 	match candidate {
-		Some((_, offset, bpm)) => {
+		Some((magnitude, offset, bpm)) => {
+			tracing::debug!(bpm, offset, magnitude, "Chose offset");
 			s.set_offset(offset as f32);
 			s.set_bpm(bpm);
 		},
-		None => {}
+		None => tracing::warn!(start = s.start(), end = s.end(), "No offset candidates for section"),
+	}
+
+	let shift_count = scores.iter().map(|v| v.len()).max().unwrap_or(0);
+	let mut data = vec![0.; bpms.len() * shift_count];
+	for (r, bpm_scores) in scores.iter().enumerate() {
+		data[r * shift_count..r * shift_count + bpm_scores.len()].copy_from_slice(bpm_scores);
 	}
+	let bpms: Vec<f64> = bpms.iter().map(|v| *v as f64).collect();
+	let shifts: Vec<f64> = (0..shift_count).map(|i| i as f64 / sr).collect();
+	Ok(OffsetLandscape {
+		bpms: ContainerRM::from_vec(U1, D!(bpms.len()), &bpms),
+		shifts: ContainerRM::from_vec(U1, D!(shift_count), &shifts),
+		scores: ContainerRM::from_vec(D!(bpms.len()), D!(shift_count), &data),
+	})
 }
 
 pub fn correct_offset(s: &mut TempoSection, smallest_fraction_shift: i32) {
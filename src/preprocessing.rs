@@ -1,38 +1,185 @@
 use litcontainers::*;
 use litdsp::*;
 use litdsp::windowed_iter::{WindowedColIter, WindowedIterMut};
+use std::cmp::Ordering::Equal;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SmoothingFilter {
+	/// Sum of a hann and a constant window.
+	HannConstant,
+	/// Running median along time.
+	Median,
+	/// Gaussian window spanning 6 standard deviations.
+	Gaussian,
+	/// Gaussian window additionally weighted by the similarity of the (l2 normalized) tempogram columns.
+	/// Keeps abrupt tempo changes sharp. Smaller range_sigma preserves more edges.
+	Bilateral { range_sigma: f64 },
+}
 
 pub fn smoothen_tempogram<R, C, S, D>(s: &S, window_dim: D)
 	-> ContainerRM<f64, R, C>
 	where R: Dim, C: Dim, S: Storage<f64, R, C>, D: Dim
 {
-	let w_hann = window::hanning(window_dim);
-	let w_ones = window::constant(window_dim);
-	let w = w_hann + &w_ones;
+	smoothen_tempogram_custom(s, window_dim, SmoothingFilter::HannConstant)
+}
+
+pub fn smoothen_tempogram_custom<R, C, S, D>(s: &S, window_dim: D, filter: SmoothingFilter)
+	-> ContainerRM<f64, R, C>
+	where R: Dim, C: Dim, S: Storage<f64, R, C>, D: Dim
+{
+	let _span = crate::diagnostics::timed(tracing::debug_span!("smoothen_tempogram", window = window_dim.value(), filter = ?filter));
+	let ret = match filter {
+		SmoothingFilter::HannConstant => {
+			let w_hann = window::hanning(window_dim);
+			let w_ones = window::constant(window_dim);
+			let w = w_hann + &w_ones;
+			apply_window_cols(s, &w)
+		},
+		SmoothingFilter::Median => median_filter_cols(s, window_dim.value()),
+		SmoothingFilter::Gaussian => apply_window_cols(s, &gaussian_window(window_dim)),
+		SmoothingFilter::Bilateral { range_sigma } => bilateral_filter_cols(s, &gaussian_window(window_dim), range_sigma),
+	};
 
-	let ret = apply_window_cols(s, &w);
 	let mut ret = subtract_mean(&ret);
 	normalize_cols_p1_inplace(&mut ret);
 	ret
 }
 
+pub fn gaussian_window<D: Dim>(window_dim: D) -> RowVec<f64, D> {
+	let center = (window_dim.value() as f64 - 1.) / 2.;
+	let sigma = (window_dim.value() as f64 / 6.).max(std::f64::EPSILON);
+	let mut w = RowVec::regspace_rows(U1, window_dim, 0.);
+	w.mapv_inplace(|x| (-0.5 * ((x - center) / sigma).powi(2)).exp());
+	w /= w.sum();
+	w
+}
+
+pub fn median_filter_cols<R, C, S>(s: &S, window_length: usize) -> ContainerRM<f64, R, C>
+	where R: Dim, C: Dim, S: Storage<f64, R, C>
+{
+	let mut ret = ContainerRM::zeros(s.row_dim(), s.col_dim());
+	let half = window_length / 2;
+	let col_count = s.col_count();
+
+	for (ri, row) in s.as_row_slice_iter().enumerate() {
+		let row: Vec<f64> = row.as_iter().cloned().collect();
+		for ci in 0..col_count {
+			let start = ci.saturating_sub(half);
+			let end = (ci + window_length.max(1) - half).min(col_count);
+			let mut frame = row[start..end].to_vec();
+			frame.sort_by(|a, b| a.partial_cmp(b).unwrap_or(Equal));
+			let mid = frame.len() / 2;
+			*ret.get_mut(ri, ci) = if frame.len() % 2 == 0 { (frame[mid - 1] + frame[mid]) / 2. } else { frame[mid] };
+		}
+	}
+
+	ret
+}
+
+pub fn bilateral_filter_cols<R, C, S, W>(s: &S, w: &RowVec<f64, W>, range_sigma: f64) -> ContainerRM<f64, R, C>
+	where R: Dim, C: Dim, S: Storage<f64, R, C>, W: Dim
+{
+	let mut ret = ContainerRM::zeros(s.row_dim(), s.col_dim());
+	let w: Vec<f64> = w.as_iter().cloned().collect();
+	let half = w.len() / 2;
+	let col_count = s.col_count();
+	let rows: Vec<Vec<f64>> = s.as_row_slice_iter().map(|r| r.as_iter().cloned().collect()).collect();
+
+	// Normalized columns to compare tempo distributions regardless of their energy
+	let norms: Vec<f64> = (0..col_count)
+		.map(|ci| rows.iter().map(|r| r[ci] * r[ci]).sum::<f64>().sqrt().max(std::f64::EPSILON))
+		.collect();
+	let col_distance = |a: usize, b: usize| rows.iter()
+		.map(|r| (r[a] / norms[a] - r[b] / norms[b]).powi(2)).sum::<f64>();
+	let range_denominator = 2. * range_sigma.powi(2).max(std::f64::EPSILON);
+
+	for ci in 0..col_count {
+		let weights: Vec<(usize, f64)> = w.iter().enumerate()
+			.filter_map(|(k, wk)| (ci + k).checked_sub(half).filter(|c| *c < col_count).map(|c| (c, *wk)))
+			.map(|(c, wk)| (c, wk * (-col_distance(ci, c) / range_denominator).exp()))
+			.collect();
+		let weight_sum: f64 = weights.iter().map(|(_, wk)| wk).sum();
+
+		for (ri, row) in rows.iter().enumerate() {
+			let v: f64 = weights.iter().map(|(c, wk)| row[*c] * wk).sum();
+			*ret.get_mut(ri, ci) = if weight_sum > 0. { v / weight_sum } else { 0. };
+		}
+	}
+
+	ret
+}
+
+/// Reinforces the 3/2 ratio on a cyclic tempogram. The axis holds one value per tempo bin (row).
 pub fn include_triplets<R, C, S, SA>(s: &S, axis: &SA, weight: f64) -> ContainerRM<f64, R, C>
-	where R: Dim, C: Dim, S: Storage<f64, R, C>, SA: RowVecStorage<f64, C>
+	where R: Dim, C: Dim, S: Storage<f64, R, C>, SA: RowVecStorage<f64, R>
 {
-	let triplet_fraction = 3. / 2.;
+	reinforce_metrical_ratios(s, axis, &[(3. / 2., weight)], true)
+}
+
+/// Adds for every tempo bin the intensity of its related tempo (bin * ratio) weighted by the bin's own intensity.
+/// Axis is either cyclic (one octave starting at its first value) or linear in which case
+/// ratios pointing outside of the axis are ignored.
+pub fn reinforce_metrical_ratios<R, C, S, SA>(s: &S, axis: &SA, ratios: &[(f64, f64)], cyclic: bool) -> ContainerRM<f64, R, C>
+	where R: Dim, C: Dim, S: Storage<f64, R, C>, SA: RowVecStorage<f64, R>
+{
+	let _span = crate::diagnostics::timed(tracing::debug_span!("reinforce_metrical_ratios", ratios = ?ratios, cyclic));
 	let mut ret = ContainerRM::zeros(s.row_dim(), s.col_dim());
 	ret.copy_from(s);
 
 	for (i, mut row) in ret.as_row_slice_mut_iter().enumerate() {
-		let triplet_val = (((triplet_fraction * axis[i]) - 1.) % 2.) + 1.;
-		let triplet_pos = find_nearest(axis, triplet_val);
-
-		row += &(s.slice_rows(triplet_pos) * &row * weight);
+		for (ratio, weight) in ratios {
+			if let Some(pos) = ratio_position(axis, ratio * axis[i], cyclic) {
+				row += &(s.slice_rows(pos) * &s.slice_rows(i) * *weight);
+			}
+		}
 	}
 
 	ret
 }
 
+/// Picks the metrical ratio whose related tempo bins correlate the most with the tempogram.
+/// Correlation is averaged over the bin pairs the ratio relates. Weights are returned but do not affect the choice.
+pub fn estimate_dominant_ratio<R, C, S, SA>(s: &S, axis: &SA, ratios: &[(f64, f64)], cyclic: bool) -> Option<(f64, f64)>
+	where R: Dim, C: Dim, S: Storage<f64, R, C>, SA: RowVecStorage<f64, R>
+{
+	let _span = crate::diagnostics::timed(tracing::debug_span!("estimate_dominant_ratio", ratios = ?ratios, cyclic));
+	if s.as_iter().all(|v| *v == 0.) {
+		tracing::warn!("Tempogram has no energy, no dominant metrical ratio");
+		return None;
+	}
+
+	ratios.iter().cloned().filter_map(|(ratio, weight)| {
+		let pairs: Vec<(usize, usize)> = (0..axis.col_count())
+			.filter_map(|i| ratio_position(axis, ratio * axis[i], cyclic).filter(|p| *p != i).map(|p| (i, p)))
+			.collect();
+		if pairs.is_empty() { return None; }
+
+		let score: f64 = pairs.iter().map(|(i, p)| {
+			s.slice_rows(*i).as_iter().zip(s.slice_rows(*p).as_iter())
+				.map(|(a, b)| (a * b).max(0.)).sum::<f64>()
+		}).sum();
+		Some((score / pairs.len() as f64, (ratio, weight)))
+	})
+		.max_by(|a, b| a.0.partial_cmp(&b.0).unwrap_or(Equal))
+		.map(|(_, r)| r)
+}
+
+fn ratio_position<R, SA>(axis: &SA, value: f64, cyclic: bool) -> Option<usize>
+	where R: Dim, SA: RowVecStorage<f64, R>
+{
+	let n = axis.col_count();
+	if n == 0 { return None; }
+
+	let (first, last) = (axis[0], axis[n - 1]);
+	if cyclic {
+		crate::bpm_to_cyclic(value, first).map(|c| find_nearest(axis, first * c))
+	} else if value >= first && value <= last {
+		Some(find_nearest(axis, value))
+	} else {
+		None
+	}
+}
+
 pub fn extract_tempo_curve<T, R, C, S, TA, SA>(s: &S, axis: &SA) -> RowVec<TA, R>
 	where T: ElementaryScalar, R: Dim, C: Dim, S: Storage<T, R, C>,
 	      TA: ElementaryScalar, SA: RowVecStorage<TA, C>
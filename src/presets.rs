@@ -0,0 +1,500 @@
+use litcontainers::*;
+use crate::{TempoExtractionSettings, TempoExtractionSettingsBuilder, SmoothingFilter, BoundaryMethod, PlotFormat, DumpFormat};
+use crate::{CyclicTempogramSettings, CyclicTempogramSettingsBuilder, Interpolation, OctavePooling};
+use crate::{ClickTrackOptions, ClickTrackOptionsBuilder, Limiter, ClickRouting};
+use std::path::{Path, PathBuf};
+use std::fs::File;
+use std::io::Read;
+use std::fmt;
+
+#[derive(Debug)]
+pub enum PresetError {
+	Io(std::io::Error),
+	Toml(toml::de::Error),
+	UnknownSetting(String),
+	/// Setting name and reason.
+	Value(String, String),
+}
+
+impl fmt::Display for PresetError {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match self {
+			PresetError::Io(e) => write!(f, "IO error: {}", e),
+			PresetError::Toml(e) => write!(f, "Invalid preset: {}", e),
+			PresetError::UnknownSetting(name) => write!(f, "Unknown setting: {}", name),
+			PresetError::Value(name, e) => write!(f, "Invalid value for {}: {}", name, e),
+		}
+	}
+}
+
+impl std::error::Error for PresetError {}
+
+impl From<std::io::Error> for PresetError {
+	fn from(e: std::io::Error) -> Self { PresetError::Io(e) }
+}
+
+impl From<toml::de::Error> for PresetError {
+	fn from(e: toml::de::Error) -> Self { PresetError::Toml(e) }
+}
+
+/// Conversion of a setting from and to its textual (flag) and toml (preset) representation.
+pub trait SettingValue: Sized {
+	fn parse_setting(s: &str) -> Result<Self, String>;
+
+	/// Toml literal or None if the value is not set.
+	fn format_setting(&self) -> Option<String>;
+}
+
+macro_rules! impl_setting_value_number {
+	($($t:ty),*) => {$(
+		impl SettingValue for $t {
+			fn parse_setting(s: &str) -> Result<Self, String> { s.trim().parse().map_err(|_| format!("'{}' is not a number", s)) }
+
+			fn format_setting(&self) -> Option<String> { Some(format!("{:?}", self)) }
+		}
+	)*}
+}
+
+impl_setting_value_number!(f64, f32, usize, u32, i32);
+
+impl SettingValue for bool {
+	fn parse_setting(s: &str) -> Result<Self, String> { s.trim().parse().map_err(|_| format!("'{}' is not a boolean", s)) }
+
+	fn format_setting(&self) -> Option<String> { Some(self.to_string()) }
+}
+
+impl SettingValue for PathBuf {
+	fn parse_setting(s: &str) -> Result<Self, String> { Ok(PathBuf::from(s)) }
+
+	fn format_setting(&self) -> Option<String> { Some(format!("{:?}", self.to_string_lossy())) }
+}
+
+/// Empty or "none" for no value.
+impl SettingValue for Option<f32> {
+	fn parse_setting(s: &str) -> Result<Self, String> {
+		match s.trim() {
+			"" | "none" => Ok(None),
+			v => f32::parse_setting(v).map(Some),
+		}
+	}
+
+	fn format_setting(&self) -> Option<String> { self.and_then(|v| v.format_setting()) }
+}
+
+macro_rules! impl_setting_value_list {
+	($($t:ty),*) => {$(
+		/// Comma separated values.
+		impl SettingValue for Vec<$t> {
+			fn parse_setting(s: &str) -> Result<Self, String> {
+				s.split(',').filter(|v| !v.trim().is_empty()).map(<$t>::parse_setting).collect()
+			}
+
+			fn format_setting(&self) -> Option<String> {
+				Some(format!("[{}]", self.iter().map(|v| format!("{:?}", v)).collect::<Vec<_>>().join(", ")))
+			}
+		}
+	)*}
+}
+
+impl_setting_value_list!(f32, f64);
+
+/// Comma separated ratio:weight pairs.
+impl SettingValue for Vec<(f64, f64)> {
+	fn parse_setting(s: &str) -> Result<Self, String> {
+		s.split(',').filter(|v| !v.trim().is_empty()).map(|pair| {
+			let mut values = pair.split(':').map(f64::parse_setting);
+			match (values.next(), values.next(), values.next()) {
+				(Some(ratio), Some(weight), None) => Ok((ratio?, weight?)),
+				_ => Err(format!("'{}' is not a ratio:weight pair", pair)),
+			}
+		}).collect()
+	}
+
+	fn format_setting(&self) -> Option<String> {
+		Some(format!("[{}]", self.iter().map(|(r, w)| format!("[{:?}, {:?}]", r, w)).collect::<Vec<_>>().join(", ")))
+	}
+}
+
+/// Regular bpm grid as start:end:step.
+impl SettingValue for RowVec<f64, Dynamic> {
+	fn parse_setting(s: &str) -> Result<Self, String> {
+		let values = s.split(':').map(f64::parse_setting).collect::<Result<Vec<_>, _>>()?;
+		match values.as_slice() {
+			[start, end, step] if *step > 0. && end >= start => {
+				let count = ((end - start) / step + 1e-9).floor() as usize + 1;
+				Ok(RowVec::regspace_step_rows(U1, D!(count), *start, *step))
+			},
+			_ => Err(format!("'{}' is not a start:end:step range", s)),
+		}
+	}
+
+	fn format_setting(&self) -> Option<String> {
+		let n = self.col_count();
+		if n == 0 { return None; }
+		let step = if n > 1 { self[1] - self[0] } else { 1. };
+		Some(format!("\"{}:{}:{}\"", self[0], self[n - 1], step))
+	}
+}
+
+/// hann-constant, median, gaussian or bilateral:<range_sigma>.
+impl SettingValue for SmoothingFilter {
+	fn parse_setting(s: &str) -> Result<Self, String> {
+		let mut parts = s.trim().splitn(2, ':');
+		match (parts.next(), parts.next()) {
+			(Some("hann-constant"), None) => Ok(SmoothingFilter::HannConstant),
+			(Some("median"), None) => Ok(SmoothingFilter::Median),
+			(Some("gaussian"), None) => Ok(SmoothingFilter::Gaussian),
+			(Some("bilateral"), sigma) => Ok(SmoothingFilter::Bilateral { range_sigma: f64::parse_setting(sigma.unwrap_or("0.3"))? }),
+			_ => Err(format!("'{}' is not a smoothing filter", s)),
+		}
+	}
+
+	fn format_setting(&self) -> Option<String> {
+		Some(match self {
+			SmoothingFilter::HannConstant => "\"hann-constant\"".to_string(),
+			SmoothingFilter::Median => "\"median\"".to_string(),
+			SmoothingFilter::Gaussian => "\"gaussian\"".to_string(),
+			SmoothingFilter::Bilateral { range_sigma } => format!("\"bilateral:{}\"", range_sigma),
+		})
+	}
+}
+
+/// checkerboard or cumulative-sum.
+impl SettingValue for BoundaryMethod {
+	fn parse_setting(s: &str) -> Result<Self, String> {
+		match s.trim() {
+			"checkerboard" => Ok(BoundaryMethod::Checkerboard),
+			"cumulative-sum" => Ok(BoundaryMethod::CumulativeSum),
+			_ => Err(format!("'{}' is not a boundary method", s)),
+		}
+	}
+
+	fn format_setting(&self) -> Option<String> {
+		Some(match self {
+			BoundaryMethod::Checkerboard => "\"checkerboard\"",
+			BoundaryMethod::CumulativeSum => "\"cumulative-sum\"",
+		}.to_string())
+	}
+}
+
+/// plotly, svg or png.
+impl SettingValue for PlotFormat {
+	fn parse_setting(s: &str) -> Result<Self, String> {
+		match s.trim() {
+			"plotly" => Ok(PlotFormat::Plotly),
+			"svg" => Ok(PlotFormat::Svg),
+			"png" => Ok(PlotFormat::Png),
+			_ => Err(format!("'{}' is not a plot format", s)),
+		}
+	}
+
+	fn format_setting(&self) -> Option<String> {
+		Some(match self {
+			PlotFormat::Plotly => "\"plotly\"",
+			PlotFormat::Svg => "\"svg\"",
+			PlotFormat::Png => "\"png\"",
+		}.to_string())
+	}
+}
+
+/// none, lit, npy, npz or csv.
+impl SettingValue for Option<DumpFormat> {
+	fn parse_setting(s: &str) -> Result<Self, String> {
+		match s.trim() {
+			"" | "none" => Ok(None),
+			"lit" => Ok(Some(DumpFormat::Lit)),
+			"npy" => Ok(Some(DumpFormat::Npy)),
+			"npz" => Ok(Some(DumpFormat::Npz)),
+			"csv" => Ok(Some(DumpFormat::Csv)),
+			_ => Err(format!("'{}' is not a dump format", s)),
+		}
+	}
+
+	fn format_setting(&self) -> Option<String> {
+		self.map(|f| match f {
+			DumpFormat::Lit => "\"lit\"",
+			DumpFormat::Npy => "\"npy\"",
+			DumpFormat::Npz => "\"npz\"",
+			DumpFormat::Csv => "\"csv\"",
+		}.to_string())
+	}
+}
+
+/// Frequency bands in Hz as comma separated low:high pairs. Empty or "none" for the default bands.
+impl SettingValue for Option<ContainerRM<f64, Dynamic, U2>> {
+	fn parse_setting(s: &str) -> Result<Self, String> {
+		if s.trim().is_empty() || s.trim() == "none" { return Ok(None); }
+		let bands = Vec::<(f64, f64)>::parse_setting(s)?;
+		if let Some((low, high)) = bands.iter().find(|(low, high)| low >= high) {
+			return Err(format!("band {}:{} is empty", low, high));
+		}
+		let data: Vec<f64> = bands.iter().flat_map(|(low, high)| vec![*low, *high]).collect();
+		Ok(Some(ContainerRM::from_vec(D!(bands.len()), U2, &data)))
+	}
+
+	fn format_setting(&self) -> Option<String> {
+		self.as_ref().map(|bands| {
+			let rows: Vec<String> = bands.as_row_slice_iter()
+				.map(|r| format!("[{}]", r.as_iter().map(|v| format!("{:?}", v)).collect::<Vec<_>>().join(", ")))
+				.collect();
+			format!("[{}]", rows.join(", "))
+		})
+	}
+}
+
+/// nearest, linear or cubic.
+impl SettingValue for Interpolation {
+	fn parse_setting(s: &str) -> Result<Self, String> {
+		match s.trim() {
+			"nearest" => Ok(Interpolation::Nearest),
+			"linear" => Ok(Interpolation::Linear),
+			"cubic" => Ok(Interpolation::Cubic),
+			_ => Err(format!("'{}' is not an interpolation", s)),
+		}
+	}
+
+	fn format_setting(&self) -> Option<String> {
+		Some(match self {
+			Interpolation::Nearest => "\"nearest\"",
+			Interpolation::Linear => "\"linear\"",
+			Interpolation::Cubic => "\"cubic\"",
+		}.to_string())
+	}
+}
+
+/// mean or max.
+impl SettingValue for OctavePooling {
+	fn parse_setting(s: &str) -> Result<Self, String> {
+		match s.trim() {
+			"mean" => Ok(OctavePooling::Mean),
+			"max" => Ok(OctavePooling::Max),
+			_ => Err(format!("'{}' is not an octave pooling", s)),
+		}
+	}
+
+	fn format_setting(&self) -> Option<String> {
+		Some(match self {
+			OctavePooling::Mean => "\"mean\"",
+			OctavePooling::Max => "\"max\"",
+		}.to_string())
+	}
+}
+
+/// hard-clip or soft:<threshold>.
+impl SettingValue for Limiter {
+	fn parse_setting(s: &str) -> Result<Self, String> {
+		let mut parts = s.trim().splitn(2, ':');
+		match (parts.next(), parts.next()) {
+			(Some("hard-clip"), None) => Ok(Limiter::HardClip),
+			(Some("soft"), threshold) => Ok(Limiter::Soft { threshold: f32::parse_setting(threshold.unwrap_or("0.9"))? }),
+			_ => Err(format!("'{}' is not a limiter", s)),
+		}
+	}
+
+	fn format_setting(&self) -> Option<String> {
+		Some(match self {
+			Limiter::HardClip => "\"hard-clip\"".to_string(),
+			Limiter::Soft { threshold } => format!("\"soft:{}\"", threshold),
+		})
+	}
+}
+
+/// mix, split or click-only.
+impl SettingValue for ClickRouting {
+	fn parse_setting(s: &str) -> Result<Self, String> {
+		match s.trim() {
+			"mix" => Ok(ClickRouting::Mix),
+			"split" => Ok(ClickRouting::Split),
+			"click-only" => Ok(ClickRouting::ClickOnly),
+			_ => Err(format!("'{}' is not a click routing", s)),
+		}
+	}
+
+	fn format_setting(&self) -> Option<String> {
+		Some(match self {
+			ClickRouting::Mix => "\"mix\"",
+			ClickRouting::Split => "\"split\"",
+			ClickRouting::ClickOnly => "\"click-only\"",
+		}.to_string())
+	}
+}
+
+/// Semicolon separated key=value pairs of a nested settings struct.
+fn parse_fields(s: &str) -> Result<Vec<(&str, &str)>, String> {
+	s.split(';').filter(|f| !f.trim().is_empty()).map(|field| {
+		let mut parts = field.splitn(2, '=');
+		match (parts.next(), parts.next()) {
+			(Some(key), Some(value)) => Ok((key.trim(), value.trim())),
+			_ => Err(format!("'{}' is not a key=value pair", field)),
+		}
+	}).collect()
+}
+
+/// Toml inline table of the fields which are set.
+fn format_fields(fields: Vec<(&str, Option<String>)>) -> Option<String> {
+	let fields: Vec<String> = fields.into_iter()
+		.filter_map(|(key, value)| value.map(|v| format!("{} = {}", key, v)))
+		.collect();
+	Some(format!("{{ {} }}", fields.join(", ")))
+}
+
+/// Fields interpolation, octave_range (start:end), octave_weights and pooling. Missing fields keep their defaults.
+impl SettingValue for CyclicTempogramSettings {
+	fn parse_setting(s: &str) -> Result<Self, String> {
+		let mut builder = CyclicTempogramSettingsBuilder::default();
+		for (key, value) in parse_fields(s)? {
+			match key {
+				"interpolation" => { builder.interpolation(Interpolation::parse_setting(value)?); },
+				"octave_range" => {
+					let range = match value {
+						"" | "none" => None,
+						v => match v.split(':').map(i32::parse_setting).collect::<Result<Vec<_>, _>>()?.as_slice() {
+							[start, end] => Some((*start, *end)),
+							_ => return Err(format!("'{}' is not a start:end octave range", v)),
+						},
+					};
+					builder.octave_range(range);
+				},
+				"octave_weights" => { builder.octave_weights(Vec::<f64>::parse_setting(value)?); },
+				"pooling" => { builder.pooling(OctavePooling::parse_setting(value)?); },
+				_ => return Err(format!("unknown field {}", key)),
+			}
+		}
+		builder.build()
+	}
+
+	fn format_setting(&self) -> Option<String> {
+		format_fields(vec![
+			("interpolation", self.interpolation().format_setting()),
+			("octave_range", self.octave_range().map(|(start, end)| format!("\"{}:{}\"", start, end))),
+			("octave_weights", self.octave_weights().format_setting()),
+			("pooling", self.pooling().format_setting()),
+		])
+	}
+}
+
+/// Fields click_gain, music_gain, ducking, ducking_release, limiter and routing. Missing fields keep their defaults.
+/// The click voices can not be represented as text and always keep their defaults.
+impl SettingValue for ClickTrackOptions {
+	fn parse_setting(s: &str) -> Result<Self, String> {
+		let mut builder = ClickTrackOptionsBuilder::default();
+		for (key, value) in parse_fields(s)? {
+			match key {
+				"click_gain" => { builder.click_gain(f32::parse_setting(value)?); },
+				"music_gain" => { builder.music_gain(f32::parse_setting(value)?); },
+				"ducking" => { builder.ducking(Option::<f32>::parse_setting(value)?); },
+				"ducking_release" => { builder.ducking_release(f32::parse_setting(value)?); },
+				"limiter" => { builder.limiter(Limiter::parse_setting(value)?); },
+				"routing" => { builder.routing(ClickRouting::parse_setting(value)?); },
+				"voices" => return Err("click voices can not be set in presets".to_string()),
+				_ => return Err(format!("unknown field {}", key)),
+			}
+		}
+		builder.build()
+	}
+
+	fn format_setting(&self) -> Option<String> {
+		format_fields(vec![
+			("click_gain", self.click_gain().format_setting()),
+			("music_gain", self.music_gain().format_setting()),
+			("ducking", self.ducking().format_setting()),
+			("ducking_release", self.ducking_release().format_setting()),
+			("limiter", self.limiter().format_setting()),
+			("routing", self.routing().format_setting()),
+		])
+	}
+}
+
+macro_rules! setting_fields {
+	($($name:ident ($flag:expr): $t:ty => $help:expr,)*) => {
+		/// Name, command line flag and description of every setting configurable through presets.
+		pub const SETTING_FIELDS: &[(&str, &str, &str)] = &[$((stringify!($name), $flag, $help)),*];
+
+		/// Parses the value and sets it on the builder.
+		pub fn apply_setting(builder: &mut TempoExtractionSettingsBuilder, name: &str, value: &str) -> Result<(), PresetError> {
+			match name {
+				$(stringify!($name) => {
+					builder.$name(<$t as SettingValue>::parse_setting(value).map_err(|e| PresetError::Value(name.to_string(), e))?);
+				},)*
+				_ => return Err(PresetError::UnknownSetting(name.to_string())),
+			}
+			Ok(())
+		}
+
+		/// Toml preset containing the value of every setting.
+		pub fn settings_to_preset(settings: &TempoExtractionSettings) -> String {
+			let mut ret = String::new();
+			$(match SettingValue::format_setting(settings.$name()) {
+				Some(v) => ret.push_str(&format!("{} = {}\n", stringify!($name), v)),
+				None => ret.push_str(&format!("# {} is not set\n", stringify!($name))),
+			})*
+			ret
+		}
+	}
+}
+
+setting_fields! {
+	analysis_band_bins ("analysis-band-bins"): Option<ContainerRM<f64, Dynamic, U2>> => "Novelty curve frequency bands in Hz as low:high pairs separated by commas",
+	tempo_window ("tempo-window"): f64 => "Analysis window length in seconds used for calculating tempogram",
+	tempo_hop_size ("tempo-hop-size"): f64 => "Analysis window hop length in seconds used for calculating tempogram",
+	scan_bpms ("scan-bpms"): RowVec<f64, Dynamic> => "BPMs which to check for tempo peaks as start:end:step",
+	ref_tempo ("ref-tempo"): f64 => "Reference tempo defining the partition of BPM into tempo octaves",
+	octave_divider ("octave-divider"): usize => "Amount of bins an octave is divided into in the cyclic tempogram",
+	cyclic_tempogram_settings ("cyclic-tempogram-settings"): CyclicTempogramSettings => "Cyclic tempogram interpolation, octave_range, octave_weights and pooling as key=value pairs separated by semicolons",
+	triplet_weight ("triplet-weight"): f64 => "Weight of the triplet intensity which will be added to its base intensity",
+	metrical_ratios ("metrical-ratios"): Vec<(f64, f64)> => "Additional metrical ratios as ratio:weight pairs separated by commas",
+	estimate_metrical_ratio ("estimate-metrical-ratio"): bool => "Only reinforce the metrical ratio which dominates the piece",
+	smooth_length ("smooth-length"): f64 => "Length in seconds over which the tempogram will be stabilized",
+	smoothing_filter ("smoothing-filter"): SmoothingFilter => "Tempogram smoothing filter: hann-constant, median, gaussian or bilateral:<range_sigma>",
+	min_section_length ("min-section-length"): f32 => "Minimum length for a tempo section in seconds",
+	max_section_length ("max-section-length"): f32 => "Maximum section length in seconds after which section is split in half",
+	boundary_snap_distance ("boundary-snap-distance"): Option<f32> => "Maximum distance in seconds to snap section boundaries to detected tempo changes",
+	boundary_method ("boundary-method"): BoundaryMethod => "Tempo change detection: checkerboard or cumulative-sum",
+	boundary_kernel_length ("boundary-kernel-length"): f32 => "Length in seconds looked at on each side of a potential tempo change",
+	tempo_multiples ("tempo-multiples"): Vec<f32> => "Tempo multiples to consider when searching for correct offset separated by commas",
+	preferred_bpm ("preferred-bpm"): f32 => "BPM around which the real bpm will be chosen",
+	bpm_rounding_precision ("bpm-rounding-precision"): f32 => "Precision of the BPM before correction",
+	bpm_doubt_window ("bpm-doubt-window"): f32 => "Window around candidate bpm which to search for a more fine and correct bpm",
+	bpm_doubt_step ("bpm-doubt-step"): f32 => "Steps size to take within doubt window to finetune bpm",
+	bpm_merge_threshold ("bpm-merge-threshold"): f32 => "Threshold to merge similar bpm together",
+	smallest_fraction_shift ("smallest-fraction-shift"): i32 => "Allow correction to shift the offset by given note subdivision",
+	verbose ("verbose"): bool => "Print progress to stderr",
+	visualize ("visualize"): bool => "Visualize",
+	plot_format ("plot-format"): PlotFormat => "Visualization renderer: plotly, svg or png",
+	save_click_track ("save-click-track"): bool => "Save the click track",
+	click_fraction ("click-fraction"): u32 => "Click every xth note",
+	click_track_options ("click-track-options"): ClickTrackOptions => "Click track click_gain, music_gain, ducking, ducking_release, limiter and routing as key=value pairs separated by semicolons",
+	dump_intermediates ("dump-intermediates"): Option<DumpFormat> => "Save intermediate arrays as lit, npy, npz or csv",
+	save_path ("save-path"): PathBuf => "Path to save the plot and clicktrack in if enabled",
+}
+
+pub fn read_preset(path: &Path) -> Result<TempoExtractionSettingsBuilder, PresetError> {
+	let mut data = String::new();
+	File::open(path)?.read_to_string(&mut data)?;
+	let mut ret = TempoExtractionSettingsBuilder::default();
+	apply_preset(&mut ret, &data)?;
+	Ok(ret)
+}
+
+/// Applies all settings of the toml preset to the builder.
+pub fn apply_preset(builder: &mut TempoExtractionSettingsBuilder, data: &str) -> Result<(), PresetError> {
+	let preset: toml::value::Table = toml::from_str(data)?;
+	for (name, value) in preset.iter() {
+		apply_setting(builder, name, &toml_to_setting(value))?;
+	}
+	Ok(())
+}
+
+fn toml_to_setting(v: &toml::Value) -> String {
+	match v {
+		toml::Value::String(s) => s.clone(),
+		toml::Value::Table(fields) => fields.iter()
+			.map(|(k, v)| format!("{}={}", k, toml_to_setting(v)))
+			.collect::<Vec<_>>().join(";"),
+		toml::Value::Array(values) => values.iter().map(|e| match e {
+			toml::Value::Array(pair) => pair.iter().map(toml_to_setting).collect::<Vec<_>>().join(":"),
+			e => toml_to_setting(e),
+		}).collect::<Vec<_>>().join(","),
+		v => v.to_string(),
+	}
+}
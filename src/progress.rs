@@ -0,0 +1,98 @@
+use std::fmt;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Stage {
+	NoveltyCurve,
+	Tempogram,
+	CyclicTempogram,
+	Preprocessing,
+	TempoCurve,
+	Boundaries,
+	OffsetEstimation,
+}
+
+impl Stage {
+	pub fn description(&self) -> &'static str {
+		match self {
+			Stage::NoveltyCurve => "Calculating novelty curve",
+			Stage::Tempogram => "Calculating tempogram",
+			Stage::CyclicTempogram => "Calculating cyclic tempogram",
+			Stage::Preprocessing => "Preprocessing and cleaning tempogram",
+			Stage::TempoCurve => "Tempo peaks extraction",
+			Stage::Boundaries => "Snapping section boundaries to tempo changes",
+			Stage::OffsetEstimation => "Tempo offset estimation",
+		}
+	}
+}
+
+/// Error returned when the observer requested cancellation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Cancelled;
+
+impl fmt::Display for Cancelled {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result { write!(f, "Tempo extraction was cancelled") }
+}
+
+impl std::error::Error for Cancelled {}
+
+/// Receives progress of the extraction stages. Cancellation is checked at every stage boundary
+/// and after every processed section. The offset search also checks it for every candidate bpm.
+pub trait ProgressObserver: Sync {
+	fn stage_enter(&self, _stage: Stage) {}
+
+	fn stage_exit(&self, _stage: Stage) {}
+
+	/// Progress within the stage between 0 and 1.
+	fn stage_progress(&self, _stage: Stage, _progress: f64) {}
+
+	fn is_cancelled(&self) -> bool { false }
+}
+
+/// Ignores all progress.
+pub struct NoProgress;
+
+impl ProgressObserver for NoProgress {}
+
+/// Prints the stages to stderr.
+pub struct PrintProgress;
+
+impl ProgressObserver for PrintProgress {
+	fn stage_enter(&self, stage: Stage) { eprintln!(" - {}", stage.description()) }
+
+	fn stage_progress(&self, stage: Stage, progress: f64) {
+		if stage == Stage::OffsetEstimation { eprintln!("   {:.0}%", progress * 100.) }
+	}
+}
+
+/// Observer which can be cancelled from another thread.
+#[derive(Default)]
+pub struct CancellationToken {
+	cancelled: AtomicBool,
+}
+
+impl CancellationToken {
+	pub fn new() -> Self { Self::default() }
+
+	pub fn cancel(&self) { self.cancelled.store(true, Ordering::SeqCst) }
+}
+
+impl ProgressObserver for CancellationToken {
+	fn is_cancelled(&self) -> bool { self.cancelled.load(Ordering::SeqCst) }
+}
+
+/// Enters the stage if not cancelled.
+pub fn enter_stage(observer: &dyn ProgressObserver, stage: Stage) -> Result<(), Cancelled> {
+	check_cancelled(observer)?;
+	observer.stage_enter(stage);
+	Ok(())
+}
+
+pub fn exit_stage(observer: &dyn ProgressObserver, stage: Stage) -> Result<(), Cancelled> {
+	observer.stage_exit(stage);
+	check_cancelled(observer)
+}
+
+pub fn check_cancelled(observer: &dyn ProgressObserver) -> Result<(), Cancelled> {
+	if observer.is_cancelled() { Err(Cancelled) } else { Ok(()) }
+}
@@ -0,0 +1,274 @@
+use litcontainers::*;
+use litaudio::*;
+use std::path::Path;
+use std::fs::File;
+use std::io::Write;
+use std::fmt::{self, Write as FmtWrite};
+use crate::{TempoSection, TempogramAnalysis};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PlotFormat {
+	/// Interactive plotly html report.
+	Plotly,
+	/// Static svg image.
+	Svg,
+	/// Static png image. Requires the png feature.
+	Png,
+}
+
+#[derive(Debug)]
+pub enum StaticPlotError {
+	Io(std::io::Error),
+	Render(String),
+}
+
+impl fmt::Display for StaticPlotError {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match self {
+			StaticPlotError::Io(e) => write!(f, "IO error: {}", e),
+			StaticPlotError::Render(e) => write!(f, "Could not render plot: {}", e),
+		}
+	}
+}
+
+impl std::error::Error for StaticPlotError {}
+
+impl From<std::io::Error> for StaticPlotError {
+	fn from(e: std::io::Error) -> Self { StaticPlotError::Io(e) }
+}
+
+const WIDTH: f64 = 1200.;
+const PANEL_HEIGHT: f64 = 160.;
+const PANEL_GAP: f64 = 50.;
+const MARGIN_LEFT: f64 = 60.;
+const MARGIN_RIGHT: f64 = 20.;
+const MARGIN_TOP: f64 = 30.;
+const MAX_HEATMAP_COLS: usize = 300;
+const MAX_HEATMAP_ROWS: usize = 120;
+
+/// Renders the report as svg or png depending on the format.
+pub fn save_static_report<L, P, S>(
+	path: &Path, format: PlotFormat, a: &S, novelty_curve: &RowVec<f64, Dynamic>, nc_sr: f64,
+	analysis: Option<&TempogramAnalysis>, sections: &Vec<TempoSection>
+) -> Result<(), StaticPlotError>
+	where L: Dim, P: SamplePackingType, S: AudioStorage<f64, U1, L, P>
+{
+	let svg = render_svg_report(a, novelty_curve, nc_sr, analysis, sections);
+	match format {
+		PlotFormat::Svg => Ok(File::create(path)?.write_all(svg.as_bytes())?),
+		PlotFormat::Png => svg_to_png(&svg, path),
+		PlotFormat::Plotly => Err(StaticPlotError::Render("Plotly is not a static format".to_string())),
+	}
+}
+
+/// Waveform, novelty curve, tempogram and cyclic tempogram with the tempo curve, section boundaries and beats.
+/// The tempogram panels are left out without an analysis.
+pub fn render_svg_report<L, P, S>(
+	a: &S, novelty_curve: &RowVec<f64, Dynamic>, nc_sr: f64,
+	analysis: Option<&TempogramAnalysis>, sections: &Vec<TempoSection>
+) -> String
+	where L: Dim, P: SamplePackingType, S: AudioStorage<f64, U1, L, P>
+{
+	let sr = a.sample_rate() as f64;
+	let duration = a.sample_count() as f64 / sr;
+	let beats = crate::beat_times(sections);
+	let panel_count = if analysis.is_some() { 4. } else { 2. };
+	let height = MARGIN_TOP + panel_count * (PANEL_HEIGHT + PANEL_GAP);
+
+	let mut svg = String::new();
+	writeln!(svg, r#"<svg xmlns="http://www.w3.org/2000/svg" width="{}" height="{}" viewBox="0 0 {} {}" font-family="sans-serif" font-size="11">"#, WIDTH, height, WIDTH, height).unwrap();
+	writeln!(svg, r#"<rect width="100%" height="100%" fill="white"/>"#).unwrap();
+
+	// Waveform as min/max envelope per pixel column
+	let panel = Panel::new(0, duration);
+	let samples: Vec<f64> = a.as_iter().cloned().collect();
+	let peak = samples.iter().fold(1e-9, |acc: f64, v| acc.max(v.abs()));
+	panel.frame(&mut svg, "Audio Wave", -peak, peak);
+	let columns = plot_width() as usize;
+	let mut path = String::new();
+	for c in 0..columns {
+		let start = c * samples.len() / columns;
+		if start >= samples.len() { break; }
+		let end = ((c + 1) * samples.len() / columns).max(start + 1).min(samples.len());
+		let (min, max) = samples[start..end].iter().fold((0., 0.), |(lo, hi): (f64, f64), v| (lo.min(*v), hi.max(*v)));
+		let x = MARGIN_LEFT + c as f64 + 0.5;
+		write!(path, "M{:.1} {:.1}L{:.1} {:.1}", x, panel.y(max, -peak, peak), x, panel.y(min, -peak, peak)).unwrap();
+	}
+	writeln!(svg, r##"<path d="{}" stroke="#4a6fa5" stroke-width="1"/>"##, path).unwrap();
+	panel.sections(&mut svg, sections, true);
+	panel.beats(&mut svg, &beats);
+
+	// Novelty curve
+	let panel = Panel::new(1, duration);
+	let nc_max = novelty_curve.as_iter().fold(1e-9, |acc: f64, v| acc.max(*v));
+	panel.frame(&mut svg, "Novelty Curve", 0., nc_max);
+	let points = novelty_curve.as_iter().enumerate().map(|(i, v)| (panel.x(i as f64 / nc_sr), panel.y(*v, 0., nc_max)));
+	polyline(&mut svg, points, "#333333");
+	panel.sections(&mut svg, sections, false);
+	panel.beats(&mut svg, &beats);
+
+	if let Some(analysis) = analysis {
+		// Tempogram with the section bpms
+		let panel = Panel::new(2, duration);
+		let (bpm_min, bpm_max) = (analysis.bpms().minimum(), analysis.bpms().maximum());
+		panel.frame(&mut svg, "Tempogram (BPM)", bpm_min, bpm_max);
+		panel.heatmap(&mut svg, &matrix_rows(analysis.tempogram_mag()), *analysis.sr());
+		for s in sections {
+			let y = panel.y(s.bpm() as f64, bpm_min, bpm_max);
+			writeln!(svg, r##"<line x1="{:.1}" y1="{:.1}" x2="{:.1}" y2="{:.1}" stroke="#ff3b30" stroke-width="2"/>"##,
+				panel.x(s.start() as f64), y, panel.x(s.end() as f64), y).unwrap();
+		}
+		panel.sections(&mut svg, sections, false);
+
+		// Cyclic tempogram with the tempo curve
+		let panel = Panel::new(3, duration);
+		let axis = analysis.cyclic_tempogram_axis();
+		let (axis_min, axis_max) = (axis.minimum(), axis.maximum());
+		panel.frame(&mut svg, "Cyclic Tempogram with Tempo Curve", axis_min, axis_max);
+		panel.heatmap(&mut svg, &matrix_rows(analysis.cyclic_tempogram()), *analysis.sr());
+		// Heatmap rows are evenly spaced while the axis is logarithmic, so the curve is drawn at its bin center
+		let bin_count = axis.col_count() as f64;
+		let points = analysis.tempo_curve().as_iter().enumerate()
+			.map(|(i, v)| (panel.x(i as f64 / *analysis.sr()), panel.y(cyclic_bin_position(*v, bin_count) + 0.5, 0., bin_count)));
+		polyline(&mut svg, points, "#ff3b30");
+		panel.sections(&mut svg, sections, false);
+		panel.time_axis(&mut svg);
+	}
+
+	writeln!(svg, "</svg>").unwrap();
+	svg
+}
+
+#[cfg(feature = "png")]
+pub fn svg_to_png(svg: &str, path: &Path) -> Result<(), StaticPlotError> {
+	let options = usvg::Options::default();
+	let tree = usvg::Tree::from_str(svg, &options.to_ref()).map_err(|e| StaticPlotError::Render(e.to_string()))?;
+	let size = tree.svg_node().size.to_screen_size();
+	let mut pixmap = tiny_skia::Pixmap::new(size.width(), size.height())
+		.ok_or_else(|| StaticPlotError::Render("Invalid image size".to_string()))?;
+	resvg::render(&tree, usvg::FitTo::Original, tiny_skia::Transform::default(), pixmap.as_mut())
+		.ok_or_else(|| StaticPlotError::Render("Could not rasterize".to_string()))?;
+	pixmap.save_png(path).map_err(|e| StaticPlotError::Render(e.to_string()))
+}
+
+#[cfg(not(feature = "png"))]
+pub fn svg_to_png(_svg: &str, _path: &Path) -> Result<(), StaticPlotError> {
+	Err(StaticPlotError::Render("Png output requires the png feature".to_string()))
+}
+
+fn plot_width() -> f64 { WIDTH - MARGIN_LEFT - MARGIN_RIGHT }
+
+struct Panel {
+	top: f64,
+	duration: f64,
+}
+
+impl Panel {
+	fn new(index: usize, duration: f64) -> Self {
+		Self { top: MARGIN_TOP + index as f64 * (PANEL_HEIGHT + PANEL_GAP), duration: duration.max(1e-9) }
+	}
+
+	fn x(&self, t: f64) -> f64 { MARGIN_LEFT + (t / self.duration).max(0.).min(1.) * plot_width() }
+
+	fn y(&self, v: f64, min: f64, max: f64) -> f64 {
+		let r = if max > min { (v - min) / (max - min) } else { 0.5 };
+		self.top + PANEL_HEIGHT * (1. - r.max(0.).min(1.))
+	}
+
+	fn bottom(&self) -> f64 { self.top + PANEL_HEIGHT }
+
+	fn frame(&self, svg: &mut String, title: &str, min: f64, max: f64) {
+		writeln!(svg, r##"<text x="{}" y="{:.1}" font-size="13" font-weight="bold">{}</text>"##, MARGIN_LEFT, self.top - 8., title).unwrap();
+		writeln!(svg, r##"<rect x="{}" y="{:.1}" width="{:.1}" height="{}" fill="none" stroke="#999999"/>"##, MARGIN_LEFT, self.top, plot_width(), PANEL_HEIGHT).unwrap();
+		writeln!(svg, r##"<text x="{}" y="{:.1}" text-anchor="end">{}</text>"##, MARGIN_LEFT - 4., self.top + 10., format_value(max)).unwrap();
+		writeln!(svg, r##"<text x="{}" y="{:.1}" text-anchor="end">{}</text>"##, MARGIN_LEFT - 4., self.bottom(), format_value(min)).unwrap();
+		self.time_axis(svg);
+	}
+
+	fn time_axis(&self, svg: &mut String) {
+		let step = tick_step(self.duration);
+		let mut t = 0.;
+		while t <= self.duration + 1e-9 {
+			let x = self.x(t);
+			writeln!(svg, r##"<line x1="{:.1}" y1="{:.1}" x2="{:.1}" y2="{:.1}" stroke="#999999"/>"##, x, self.bottom(), x, self.bottom() + 4.).unwrap();
+			writeln!(svg, r##"<text x="{:.1}" y="{:.1}" text-anchor="middle">{}s</text>"##, x, self.bottom() + 15., format_value(t)).unwrap();
+			t += step;
+		}
+	}
+
+	/// Rows are drawn bottom to top, both dimensions are reduced by taking the maximum.
+	fn heatmap(&self, svg: &mut String, rows: &Vec<Vec<f64>>, sr: f64) {
+		let (row_count, col_count) = (rows.len(), rows.first().map(|r| r.len()).unwrap_or(0));
+		if row_count == 0 || col_count == 0 { return; }
+		let max = rows.iter().flat_map(|r| r.iter()).fold(1e-9, |acc: f64, v| acc.max(*v));
+		let (cells_y, cells_x) = (row_count.min(MAX_HEATMAP_ROWS), col_count.min(MAX_HEATMAP_COLS));
+		let cell_height = PANEL_HEIGHT / cells_y as f64;
+
+		for cy in 0..cells_y {
+			let row_range = (cy * row_count / cells_y)..((cy + 1) * row_count / cells_y).max(cy * row_count / cells_y + 1);
+			for cx in 0..cells_x {
+				let col_range = (cx * col_count / cells_x)..((cx + 1) * col_count / cells_x).max(cx * col_count / cells_x + 1);
+				let v = rows[row_range.clone()].iter()
+					.flat_map(|r| r[col_range.clone()].iter())
+					.fold(0., |acc: f64, v| acc.max(*v));
+				let x0 = self.x(col_range.start as f64 / sr);
+				let x1 = self.x(col_range.end as f64 / sr);
+				let y = self.bottom() - (cy + 1) as f64 * cell_height;
+				writeln!(svg, r##"<rect x="{:.1}" y="{:.1}" width="{:.1}" height="{:.1}" fill="{}"/>"##,
+					x0, y, (x1 - x0).max(0.5), cell_height + 0.2, color(v / max)).unwrap();
+			}
+		}
+	}
+
+	fn sections(&self, svg: &mut String, sections: &Vec<TempoSection>, labels: bool) {
+		for s in sections {
+			let x = self.x(s.start() as f64);
+			writeln!(svg, r##"<line x1="{:.1}" y1="{:.1}" x2="{:.1}" y2="{:.1}" stroke="#ff9500" stroke-width="1.5" stroke-dasharray="4 3"/>"##,
+				x, self.top, x, self.bottom()).unwrap();
+			if labels {
+				writeln!(svg, r##"<text x="{:.1}" y="{:.1}" fill="#c05c00">{:.2} BPM</text>"##, x + 3., self.top + 12., s.bpm()).unwrap();
+			}
+		}
+	}
+
+	fn beats(&self, svg: &mut String, beats: &Vec<f32>) {
+		let mut path = String::new();
+		for b in beats {
+			let x = self.x(*b as f64);
+			write!(path, "M{:.1} {:.1}L{:.1} {:.1}", x, self.bottom() - 8., x, self.bottom()).unwrap();
+		}
+		writeln!(svg, r##"<path d="{}" stroke="#34c759" stroke-width="1"/>"##, path).unwrap();
+	}
+}
+
+fn polyline(svg: &mut String, points: impl Iterator<Item=(f64, f64)>, stroke: &str) {
+	let points: Vec<String> = points.map(|(x, y)| format!("{:.1},{:.1}", x, y)).collect();
+	writeln!(svg, r##"<polyline points="{}" fill="none" stroke="{}" stroke-width="1"/>"##, points.join(" "), stroke).unwrap();
+}
+
+fn matrix_rows<R: Dim, C: Dim>(s: &ContainerRM<f64, R, C>) -> Vec<Vec<f64>> {
+	s.as_row_slice_iter().map(|r| r.as_iter().cloned().collect()).collect()
+}
+
+/// Fractional bin index of a value on the cyclic axis where bin k holds 2^(k / bin_count).
+pub fn cyclic_bin_position(v: f64, bin_count: f64) -> f64 { v.log2() * bin_count }
+
+fn tick_step(duration: f64) -> f64 {
+	[1., 2., 5., 10., 15., 30., 60., 120., 300., 600.].iter().cloned()
+		.find(|step| duration / step <= 12.)
+		.unwrap_or(duration / 10.)
+}
+
+fn format_value(v: f64) -> String {
+	if v.abs() >= 100. || v.fract() == 0. { format!("{:.0}", v) } else { format!("{:.2}", v) }
+}
+
+/// Viridis like color map for values between 0 and 1.
+fn color(v: f64) -> String {
+	const STOPS: [(f64, f64, f64); 5] = [(68., 1., 84.), (59., 82., 139.), (33., 145., 140.), (94., 201., 98.), (253., 231., 37.)];
+	let pos = v.max(0.).min(1.) * (STOPS.len() - 1) as f64;
+	let i = (pos.floor() as usize).min(STOPS.len() - 2);
+	let f = pos - i as f64;
+	let (a, b) = (STOPS[i], STOPS[i + 1]);
+	format!("rgb({:.0},{:.0},{:.0})", a.0 + (b.0 - a.0) * f, a.1 + (b.1 - a.1) * f, a.2 + (b.2 - a.2) * f)
+}
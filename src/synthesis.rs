@@ -0,0 +1,200 @@
+use litcontainers::*;
+use litaudio::*;
+use crate::{TempoSection, ClickKind, ClickVoice, ClickVoices, ClickVoicesBuilder, ClickSource};
+use std::f64;
+
+/// Ground truth section of a synthetic signal.
+#[derive(Debug, Clone, Builder, Getters)]
+pub struct SyntheticSection {
+	start: f32,
+	end: f32,
+	/// Tempo at the section start.
+	bpm: f32,
+	/// Tempo at the section end. Tempo changes linearly in time. Constant tempo if None.
+	#[builder(default = "None")]
+	end_bpm: Option<f32>,
+	/// Time of the first downbeat. Defaults to the section start.
+	#[builder(default = "None")]
+	offset: Option<f32>,
+	#[builder(default = "4")]
+	beats_per_bar: u32,
+	/// Delay of the off-beat eighth notes as fraction of half a beat. 1/3 gives a triplet shuffle.
+	#[builder(default = "0.")]
+	swing: f32,
+}
+
+#[derive(Debug, Clone, Builder, Getters)]
+pub struct SynthesisSettings {
+	#[builder(default = "22050.")]
+	sr: f64,
+	/// Amplitude of the added white noise.
+	#[builder(default = "0.")]
+	noise_level: f32,
+	/// Reverb decay time in seconds (-60dB) and wet mix. No reverb if None.
+	#[builder(default = "None")]
+	reverb: Option<(f32, f32)>,
+	/// Seed of the noise for deterministic signals.
+	#[builder(default = "1")]
+	seed: u64,
+}
+
+impl Default for SynthesisSettings {
+	fn default() -> Self { SynthesisSettingsBuilder::default().build().unwrap() }
+}
+
+#[derive(Debug, Clone, Getters)]
+pub struct SyntheticSignal {
+	audio: AudioDeinterleaved<f64, U1, Dynamic>,
+	/// Exact beat times.
+	beats: Vec<f32>,
+	/// Exact bar start times.
+	downbeats: Vec<f32>,
+	/// Sections with their mean tempo and first beat as offset.
+	sections: Vec<TempoSection>,
+}
+
+/// Renders a percussive signal (kick on downbeats, snare on other beats and hi-hat on eighth notes)
+/// following the tempo map.
+pub fn synthesize_tempo_map(sections: &Vec<SyntheticSection>, settings: &SynthesisSettings) -> SyntheticSignal {
+	let sr = settings.sr;
+	let mut rng = XorShift::new(settings.seed);
+	let kit = drum_kit(sr, &mut rng);
+
+	let mut positions = Vec::new();
+	let mut beats = Vec::new();
+	let mut downbeats = Vec::new();
+	let mut tempo_sections = Vec::new();
+	for s in sections {
+		let section_beats = section_beat_times(s);
+		for (i, pair) in section_beats.windows(2).enumerate() {
+			let kind = if i as u32 % s.beats_per_bar.max(1) == 0 { ClickKind::Downbeat } else { ClickKind::Beat };
+			let offbeat = pair[0] + (pair[1] - pair[0]) * (0.5 + s.swing / 2.);
+			positions.push((pair[0], kind));
+			positions.push((pair[0], ClickKind::Subdivision));
+			positions.push((offbeat, ClickKind::Subdivision));
+		}
+		downbeats.extend(section_beats.iter().step_by(s.beats_per_bar.max(1) as usize).filter(|b| **b < s.end));
+		beats.extend(section_beats.iter().filter(|b| **b < s.end));
+
+		let bpm = (s.bpm + s.end_bpm.unwrap_or(s.bpm)) / 2.;
+		tempo_sections.push(TempoSection::new(s.start, s.end, bpm, section_beats.first().cloned().unwrap_or(s.start)));
+	}
+
+	let len = (sections.iter().map(|s| s.end).fold(0., f32::max) as f64 * sr).round() as usize;
+	let drums = crate::click_track_from_accented_positions(&positions, sr, D!(len), &kit);
+	let mut samples: Vec<f64> = drums.as_iter().map(|v| *v as f64).collect();
+
+	if let Some((decay, mix)) = settings.reverb {
+		samples = reverb(&samples, sr, decay, mix);
+	}
+	if settings.noise_level > 0. {
+		samples.iter_mut().for_each(|v| *v += rng.next_signed() * settings.noise_level as f64);
+	}
+
+	let mut audio = AudioDeinterleaved::new(DeinterleavedStorage::zeros(U1, D!(len)), sr.round() as i32);
+	audio.as_iter_mut().zip(samples.iter()).for_each(|(o, v)| *o = *v);
+
+	SyntheticSignal { audio, beats, downbeats, sections: tempo_sections }
+}
+
+/// Beat times of the section including the first beat after the section end.
+fn section_beat_times(s: &SyntheticSection) -> Vec<f32> {
+	let duration = (s.end - s.start).max(std::f32::EPSILON) as f64;
+	let slope = (s.end_bpm.unwrap_or(s.bpm) - s.bpm) as f64 / duration / 60.; // Beats per s^2
+	let offset = s.offset.unwrap_or(s.start) as f64;
+	let speed = s.bpm as f64 / 60. + slope * (offset - s.start as f64); // Beats per s at offset
+
+	// Time after offset at which the phase reaches k beats
+	let beat_time = |k: f64| if slope.abs() < 1e-9 {
+		k / speed
+	} else {
+		(-speed + (speed * speed + 2. * slope * k).max(0.).sqrt()) / slope
+	};
+
+	let mut ret = Vec::new();
+	for k in 0.. {
+		let t = offset + beat_time(k as f64);
+		if !t.is_finite() || (k > 0 && t <= ret.last().cloned().unwrap_or(0.) as f64) { break; }
+		ret.push(t as f32);
+		if t >= s.end as f64 { break; }
+	}
+	ret
+}
+
+fn drum_kit(sr: f64, rng: &mut XorShift) -> ClickVoices {
+	let len = |duration: f64| (duration * sr) as usize;
+	let envelope = |i: usize, decay: f64| (-(i as f64 / sr) / decay).exp();
+
+	// Kick: sine sweeping down from 150 to 50 Hz
+	let mut phase = 0.;
+	let kick: Vec<f32> = (0..len(0.3)).map(|i| {
+		let freq = 50. + 100. * envelope(i, 0.03);
+		phase += 2. * f64::consts::PI * freq / sr;
+		(phase.sin() * envelope(i, 0.1)) as f32
+	}).collect();
+
+	// Snare: noise burst with a body tone
+	let snare: Vec<f32> = (0..len(0.2)).map(|i| {
+		let tone = (2. * f64::consts::PI * 180. * i as f64 / sr).sin() * envelope(i, 0.05);
+		((rng.next_signed() * 0.7 + tone * 0.5) * envelope(i, 0.06)) as f32
+	}).collect();
+
+	// Hi-hat: differentiated (high passed) noise
+	let mut last = 0.;
+	let hat: Vec<f32> = (0..len(0.05)).map(|i| {
+		let noise = rng.next_signed();
+		let v = (noise - last) * 0.5 * envelope(i, 0.01);
+		last = noise;
+		v as f32
+	}).collect();
+
+	let voice = |data: Vec<f32>, gain: f32| ClickVoice::new(ClickSource::Sample { data, sr }, gain);
+	ClickVoicesBuilder::default()
+		.downbeat(voice(kick, 0.8))
+		.beat(voice(snare, 0.5))
+		.subdivision(voice(hat, 0.3))
+		.build().unwrap()
+}
+
+/// Schroeder reverb: parallel feedback combs followed by allpass filters.
+fn reverb(s: &Vec<f64>, sr: f64, decay: f32, mix: f32) -> Vec<f64> {
+	let comb_delays = [0.0297, 0.0371, 0.0411, 0.0437];
+	let mut wet = vec![0.; s.len()];
+	for delay in comb_delays.iter() {
+		let d = ((delay * sr) as usize).max(1);
+		let gain = 10f64.powf(-3. * delay / decay.max(0.01) as f64);
+		let mut buffer = vec![0.; s.len()];
+		for i in 0..s.len() {
+			buffer[i] = s[i] + if i >= d { gain * buffer[i - d] } else { 0. };
+			wet[i] += buffer[i] / comb_delays.len() as f64;
+		}
+	}
+
+	for delay in [0.005, 0.0017].iter() {
+		let d = ((delay * sr) as usize).max(1);
+		let g = 0.7;
+		let input = wet.clone();
+		for i in 0..wet.len() {
+			let delayed_in = if i >= d { input[i - d] } else { 0. };
+			let delayed_out = if i >= d { wet[i - d] } else { 0. };
+			wet[i] = -g * input[i] + delayed_in + g * delayed_out;
+		}
+	}
+
+	s.iter().zip(wet.iter()).map(|(dry, wet)| dry * (1. - mix as f64) + wet * mix as f64).collect()
+}
+
+/// Small deterministic noise generator.
+struct XorShift(u64);
+
+impl XorShift {
+	fn new(seed: u64) -> Self { XorShift(seed.max(1)) }
+
+	/// Uniform in [-1, 1).
+	fn next_signed(&mut self) -> f64 {
+		self.0 ^= self.0 << 13;
+		self.0 ^= self.0 >> 7;
+		self.0 ^= self.0 << 17;
+		(self.0 >> 11) as f64 / (1u64 << 52) as f64 - 1.
+	}
+}
@@ -4,7 +4,7 @@ use litdsp::*;
 use litplot::plotly::*;
 use std::path::PathBuf;
 use std::cmp::Ordering::Equal;
-use crate::TempoSection;
+use crate::{TempoSection, ProgressObserver, Stage, Cancelled, enter_stage, exit_stage};
 
 #[derive(Debug, Clone, Builder, Getters)]
 pub struct TempoExtractionSettings {
@@ -26,18 +26,39 @@ pub struct TempoExtractionSettings {
 	/// Determines the amount of bins and octave (based on ref_tempo) is divided into thus the dimensionality of cyclic tempogram.
 	#[builder(default = "120")]
 	octave_divider: usize,
+	/// Interpolation, octave range, octave weights and pooling used for calculating cyclic tempogram.
+	#[builder(default = "crate::CyclicTempogramSettings::default()")]
+	cyclic_tempogram_settings: crate::CyclicTempogramSettings,
 	/// Weight of the triplet intensity which will be adeed to its base intensity.
 	#[builder(default = "3.")]
 	triplet_weight: f64,
+	/// Additional metrical ratios with their weights (e.g. 4:3, 5:4) reinforced like the triplets.
+	#[builder(default = "Vec::new()")]
+	metrical_ratios: Vec<(f64, f64)>,
+	/// Only reinforce the metrical ratio which dominates the piece instead of all of them.
+	#[builder(default = "false")]
+	estimate_metrical_ratio: bool,
 	/// Length in seconds over which the tempogram will be stabilized to extract a steady tempo.
 	#[builder(default = "20.")]
 	smooth_length: f64,
+	/// Filter applied along time to stabilize the tempogram.
+	#[builder(default = "crate::SmoothingFilter::HannConstant")]
+	smoothing_filter: crate::SmoothingFilter,
 	/// Minimum length for a tempo section in seconds.
 	#[builder(default = "10.")]
 	min_section_length: f32,
 	/// Maximum section length in seconds after which section is split in half.
 	#[builder(default = "40.")]
 	max_section_length: f32,
+	/// Maximum distance in seconds to snap section boundaries to detected tempo changes. Disabled if None.
+	#[builder(default = "None")]
+	boundary_snap_distance: Option<f32>,
+	/// Method used to detect tempo changes in the cyclic tempogram.
+	#[builder(default = "crate::BoundaryMethod::Checkerboard")]
+	boundary_method: crate::BoundaryMethod,
+	/// Length in seconds looked at on each side of a potential tempo change.
+	#[builder(default = "5.")]
+	boundary_kernel_length: f32,
 	/// Tempo multiples to consider when searching for correct offset.
 	#[builder(default = "vec![1., 2., 4., 6.]")]
 	tempo_multiples: Vec<f32>,
@@ -59,18 +80,30 @@ pub struct TempoExtractionSettings {
 	/// Allow correction to shift the offset by given note subdivision.
 	#[builder(default = "4")]
 	smallest_fraction_shift: i32,
-	/// Verbose.
+	/// Print progress to stderr in extract_tempo.
 	#[builder(default = "false")]
 	verbose: bool,
 	/// Visualize.
 	#[builder(default = "false")]
 	visualize: bool,
+	/// Renderer used for the visualization.
+	#[builder(default = "crate::PlotFormat::Plotly")]
+	plot_format: crate::PlotFormat,
 	/// Save the click track.
 	#[builder(default = "false")]
 	save_click_track: bool,
 	/// Click every xth note.
 	#[builder(default = "12")]
 	click_fraction: u32,
+	/// Click sounds and mixing of the click track.
+	#[builder(default = "crate::ClickTrackOptions::default()")]
+	click_track_options: crate::ClickTrackOptions,
+	/// Known bpm range, fixed bpm or sections to refine.
+	#[builder(default = "crate::TempoConstraints::default()")]
+	constraints: crate::TempoConstraints,
+	/// Format to save the intermediate results in (in save_path/intermediates). Disabled if None.
+	#[builder(default = "None")]
+	dump_intermediates: Option<crate::DumpFormat>,
 	/// Path to save the plot and clicktrack in if enabled.
 	#[builder(default = "std::env::current_dir().unwrap()")]
 	save_path: PathBuf,
@@ -92,22 +125,262 @@ impl TempoExtractionSettings {
 	pub fn get_min_section_length(&self, sr: f64) -> usize {
 		(self.min_section_length as f64 * sr).round() as usize
 	}
+
+	pub fn get_boundary_kernel_length(&self, sr: f64) -> usize {
+		(self.boundary_kernel_length as f64 * sr).round() as usize
+	}
 }
 
+/// Intermediate results of the tempogram stages.
+#[derive(Debug, Clone, Getters)]
+pub struct TempogramAnalysis {
+	/// Scanned bpms (tempogram rows) after applying the constraints.
+	bpms: RowVec<f64, Dynamic>,
+	/// Normalized complex tempogram.
+	tempogram: ContainerRM<c64, Dynamic, Dynamic>,
+	/// Magnitude of the normalized tempogram.
+	tempogram_mag: ContainerRM<f64, Dynamic, Dynamic>,
+	/// Sample rate of the tempogram columns.
+	sr: f64,
+	cyclic_tempogram: ContainerRM<f64, Dynamic, Dynamic>,
+	cyclic_tempogram_axis: RowVec<f64, Dynamic>,
+	/// Cyclic tempogram with metrical reinforcement and smoothing applied.
+	smooth_tempogram: ContainerRM<f64, Dynamic, Dynamic>,
+	/// Length corrected tempo curve on the cyclic axis.
+	tempo_curve: RowVec<f64, Dynamic>,
+}
+
+/// Extracts tempo sections printing the progress if verbose.
 pub fn extract_tempo<L, P, S>(a: &S, settings: &TempoExtractionSettings) -> Vec<TempoSection>
 	where L: Dim, P: SamplePackingType, S: AudioStorage<f64, U1, L, P>
 {
-	let sr = a.sample_rate() as f64;
+	let observer: &dyn ProgressObserver = if *settings.verbose() { &crate::PrintProgress } else { &crate::NoProgress };
+	extract_tempo_observed(a, settings, observer).unwrap()
+}
+
+/// Extracts tempo sections reporting the progress to the observer. Stops if the observer cancels.
+pub fn extract_tempo_observed<L, P, S>(a: &S, settings: &TempoExtractionSettings, observer: &dyn ProgressObserver)
+	-> Result<Vec<TempoSection>, Cancelled>
+	where L: Dim, P: SamplePackingType, S: AudioStorage<f64, U1, L, P>
+{
+	extract_tempo_detailed(a, settings, observer).map(|e| e.sections)
+}
+
+/// Runs all stages after the novelty curve. Allows using onset strengths from other tools or symbolic onsets
+/// (see onsets_to_novelty_curve).
+pub fn extract_tempo_from_novelty_curve(novelty_curve: &RowVec<f64, Dynamic>, nc_sr: f64, settings: &TempoExtractionSettings, observer: &dyn ProgressObserver)
+	-> Result<Vec<TempoSection>, Cancelled>
+{
+	let _span = crate::diagnostics::timed(tracing::info_span!("extract_tempo_from_novelty_curve", nc_sr, samples = novelty_curve.col_count()));
+	if settings.constraints().fixed_bpm().is_some() {
+		// Only the offset has to be estimated
+		let sections = fixed_bpm_sections(novelty_curve, nc_sr, settings);
+		return refine_tempo_sections(novelty_curve, nc_sr, sections, settings, observer).map(|(sections, _)| sections);
+	}
+	let analysis = analyze_tempogram(novelty_curve, nc_sr, settings, observer)?;
+	extract_tempo_sections(novelty_curve, nc_sr, &analysis, settings, observer)
+}
+
+/// Results of all extraction stages.
+#[derive(Debug, Clone, Getters)]
+pub struct TempoExtraction {
+	novelty_curve: RowVec<f64, Dynamic>,
+	/// Sample rate of the novelty curve.
+	nc_sr: f64,
+	/// Onset detection function per analysis band.
+	band_odf: ContainerRM<f64, Dynamic, Dynamic>,
+	band_odf_sr: f64,
+	/// Frequency bands in Hz of the band odf rows as low and high edge.
+	bands: ContainerRM<f64, Dynamic, U2>,
+	/// Tempogram stages. None if the bpm is fixed and only the offsets were estimated.
+	analysis: Option<TempogramAnalysis>,
+	sections: Vec<TempoSection>,
+	/// Offset scores searched for each section.
+	offset_landscapes: Vec<crate::OffsetLandscape>,
+}
+
+/// Like extract_tempo_observed but also returns the intermediate results.
+pub fn extract_tempo_detailed<L, P, S>(a: &S, settings: &TempoExtractionSettings, observer: &dyn ProgressObserver)
+	-> Result<TempoExtraction, Cancelled>
+	where L: Dim, P: SamplePackingType, S: AudioStorage<f64, U1, L, P>
+{
+	let _span = crate::diagnostics::timed(tracing::info_span!("extract_tempo"));
+	tracing::info!(
+		tempo_window = settings.tempo_window,
+		tempo_hop_size = settings.tempo_hop_size,
+		ref_tempo = settings.ref_tempo,
+		octave_divider = settings.octave_divider,
+		smooth_length = settings.smooth_length,
+		smoothing_filter = ?settings.smoothing_filter,
+		min_section_length = settings.min_section_length,
+		preferred_bpm = settings.preferred_bpm,
+		"Extracting tempo"
+	);
+	let (novelty_curve, nc_sr, band_odf, band_odf_sr) = extract_novelty_curve_with_bands(a, settings, observer)?;
+	let analysis = if settings.constraints().fixed_bpm().is_some() { None } else { Some(analyze_tempogram(&novelty_curve, nc_sr, settings, observer)?) };
+	let (tempo_sections, offset_landscapes) = match &analysis {
+		Some(analysis) => extract_tempo_sections_detailed(&novelty_curve, nc_sr, analysis, settings, observer)?,
+		// Only the offset has to be estimated
+		None => refine_tempo_sections(&novelty_curve, nc_sr, fixed_bpm_sections(&novelty_curve, nc_sr, settings), settings, observer)?,
+	};
+
+	// Save a click track
+	if *settings.save_click_track() {
+		let path = settings.save_path().join("click_track.mp3");
+		let mut click_audio = AudioDeinterleaved::new(DeinterleavedStorage::zeros(a.channel_dim(), a.sample_dim()), a.sample_rate());
+		click_audio.as_iter_mut().zip(a.as_iter()).for_each(|(o, i)| *o = *i as f32);
+		crate::save_tempo_click_track(&path, &click_audio, &tempo_sections, *settings.click_fraction(), settings.click_track_options()).unwrap();
+	}
+
+	// Plot data
+	if *settings.visualize() && *settings.plot_format() != crate::PlotFormat::Plotly {
+		let extension = if *settings.plot_format() == crate::PlotFormat::Png { "png" } else { "svg" };
+		let path = settings.save_path().join(format!("plot.{}", extension));
+		if let Err(e) = crate::save_static_report(&path, *settings.plot_format(), a, &novelty_curve, nc_sr, analysis.as_ref(), &tempo_sections) {
+			tracing::warn!(error = %e, "Could not save plot");
+		}
+	} else if *settings.visualize() {
+		let sr = a.sample_rate() as f64;
+		let audio_x = litdsp::wave::calculate_time(a.col_dim(), sr);
+		let plot = Plot::new("audio")
+			.add_chart(
+				LineBuilder::default()
+					.identifier("audio")
+					.data(XYData::new(
+						provider_litcontainer(Fetch::Remote, &audio_x, None).unwrap(),
+						provider_litcontainer(Fetch::Remote, a, None).unwrap(),
+					))
+					.name("Audio Wave")
+					.build()
+					.unwrap()
+			)
+			.add_chart(
+				LineBuilder::default()
+					.identifier("chart_1")
+					.data(XYData::new(
+						provider_litcontainer(Fetch::Remote, &litdsp::wave::calculate_time(novelty_curve.col_dim(), nc_sr), Some("chart_1_x".into())).unwrap(),
+						provider_litcontainer(Fetch::Remote, &(&novelty_curve / novelty_curve.maximum()), Some("chart_1_y".into())).unwrap(),
+					))
+					.name("Novelty Curve")
+					.build()
+					.unwrap()
+			);
+		let plot = add_result_overlay(plot, &tempo_sections, -1., 1.);
+
+		let mut report = Report::new("Novelty Curve").add_node(plot);
+		if let Some(analysis) = &analysis {
+			let (tempogram, tempogram_sr, tempogram_mag_t) = (&analysis.tempogram, analysis.sr, &analysis.tempogram_mag);
+			let (cyclic_tempogram, cyclic_tempogram_axis) = (&analysis.cyclic_tempogram, &analysis.cyclic_tempogram_axis);
+			let (smooth_tempogram, tempo_curve) = (&analysis.smooth_tempogram, &analysis.tempo_curve);
 
-	if *settings.verbose() { println!("Processing audio file.") }
+			let plot2 = Plot::new("tempogram")
+				.add_chart(
+					HeatmapBuilder::default()
+						.data(XYZData::new(
+							provider_litcontainer(Fetch::Remote, &litdsp::wave::calculate_time(tempogram.col_dim(), tempogram_sr), None).unwrap(),
+							provider_litcontainer(Fetch::Remote, &analysis.bpms, None).unwrap(),
+							provider_litcontainer(Fetch::Remote, tempogram_mag_t, None).unwrap(),
+						))
+						.name("Tempogram")
+						.build().unwrap()
+				);
 
-	if *settings.verbose() { println!(" - Calculating novelty curve") }
+			let plot3 = Plot::new("tempogram_cyclic")
+				.add_chart(
+					HeatmapBuilder::default()
+						.data(XYZData::new(
+							provider_litcontainer(Fetch::Remote, &litdsp::wave::calculate_time(tempogram.col_dim(), tempogram_sr), None).unwrap(),
+							provider_litcontainer(Fetch::Remote, cyclic_tempogram_axis, None).unwrap(),
+							provider_litcontainer(Fetch::Remote, cyclic_tempogram, None).unwrap(),
+						))
+						.name("Cyclic Tempogram")
+						.build().unwrap()
+				);
+
+			let plot4 = Plot::new("smooth_tempogram")
+				.add_chart(
+					HeatmapBuilder::default()
+						.data(XYZData::new(
+							provider_litcontainer(Fetch::Remote, &litdsp::wave::calculate_time(tempogram.col_dim(), tempogram_sr), None).unwrap(),
+							provider_litcontainer(Fetch::Remote, cyclic_tempogram_axis, None).unwrap(),
+							provider_litcontainer(Fetch::Remote, smooth_tempogram, None).unwrap(),
+						))
+						.name("Smooth Tempogram")
+						.build().unwrap()
+				)
+				.add_chart(
+					LineBuilder::default()
+						.data(XYData::new(
+							provider_litcontainer(Fetch::Remote, &litdsp::wave::calculate_time(tempogram.col_dim(), tempogram_sr), None).unwrap(),
+							provider_litcontainer(Fetch::Remote, tempo_curve, None).unwrap()
+						))
+						.name("Tempo Curve")
+						.build().unwrap()
+				);
+
+			report = report
+				.add_node(plot2)
+				.add_node(plot3)
+				.add_node(plot4);
+		}
+
+		for (i, (landscape, s)) in offset_landscapes.iter().zip(tempo_sections.iter()).enumerate() {
+			let name = format!("Offset scores {:.2}s - {:.2}s (shift vs bpm)", s.start(), s.end());
+			let landscape_plot = Plot::new(format!("offset_landscape_{}", i).as_str())
+				.add_chart(
+					HeatmapBuilder::default()
+						.data(XYZData::new(
+							provider_litcontainer(Fetch::Remote, landscape.shifts(), None).unwrap(),
+							provider_litcontainer(Fetch::Remote, landscape.bpms(), None).unwrap(),
+							provider_litcontainer(Fetch::Remote, landscape.scores(), None).unwrap(),
+						))
+						.name(name.as_str())
+						.build().unwrap()
+				);
+			report = report.add_node(landscape_plot);
+		}
+
+		let path = settings.save_path().join("plot");
+		report.force_save(path.as_path()).unwrap();
+	}
+
+	let bands = analysis_bands(settings, a.sample_rate() as f64);
+	let ret = TempoExtraction { novelty_curve, nc_sr, band_odf, band_odf_sr, bands, analysis, sections: tempo_sections, offset_landscapes };
+	if let Some(format) = settings.dump_intermediates() {
+		let path = settings.save_path().join("intermediates");
+		if let Err(e) = crate::save_intermediates(&path, *format, &ret) {
+			tracing::warn!(error = %e, "Could not save intermediates");
+		}
+	}
+	Ok(ret)
+}
+
+pub fn extract_novelty_curve<L, P, S>(a: &S, settings: &TempoExtractionSettings, observer: &dyn ProgressObserver)
+	-> Result<(RowVec<f64, Dynamic>, f64), Cancelled>
+	where L: Dim, P: SamplePackingType, S: AudioStorage<f64, U1, L, P>
+{
+	extract_novelty_curve_with_bands(a, settings, observer).map(|(novelty_curve, nc_sr, _, _)| (novelty_curve, nc_sr))
+}
+
+/// Novelty curve with its sample rate, followed by the band odf and its sample rate.
+pub fn extract_novelty_curve_with_bands<L, P, S>(a: &S, settings: &TempoExtractionSettings, observer: &dyn ProgressObserver)
+	-> Result<(RowVec<f64, Dynamic>, f64, ContainerRM<f64, Dynamic, Dynamic>, f64), Cancelled>
+	where L: Dim, P: SamplePackingType, S: AudioStorage<f64, U1, L, P>
+{
+	let sr = a.sample_rate() as f64;
+
+	enter_stage(observer, Stage::NoveltyCurve)?;
+	let _span = crate::diagnostics::timed(tracing::info_span!("novelty_curve_stage"));
+	for issue in crate::inspect_audio(a, sr, *settings.tempo_window()) {
+		match issue {
+			crate::AudioIssue::Short { duration } => tracing::warn!(duration, "Audio is shorter than the tempo window"),
+			crate::AudioIssue::NearSilent { peak } => tracing::warn!(peak, "Audio is nearly silent"),
+			crate::AudioIssue::Clipped { fraction } => tracing::warn!(fraction, "Audio is clipped"),
+		}
+	}
 	// Calculate novelty curve / odf
-	let bands = settings.analysis_band_bins().as_ref().map(|c| c.clone_owned()).unwrap_or({
-		let ret = crate::default_audio_bands(sr);
-		ret.transmute_dims(D!(ret.row_count()), ret.col_dim(), ret.row_stride_dim(), ret.col_stride_dim()).owned()
-	});
-	let (novelty_curve, nc_sr) = crate::calculate_novelty_curve(
+	let bands = analysis_bands(settings, sr);
+	let ret = crate::calculate_novelty_curve_with_bands(
 		a,
 		sr,
 		Dynamic::new((1024. * sr / 22050.) as usize),
@@ -115,43 +388,122 @@ pub fn extract_tempo<L, P, S>(a: &S, settings: &TempoExtractionSettings) -> Vec<
 		&bands,
 		crate::NCSettingsBuilder::default().build().unwrap()
 	);
+	exit_stage(observer, Stage::NoveltyCurve)?;
+	Ok(ret)
+}
+
+/// Frequency bands in Hz used for the novelty curve. Defaults to default_audio_bands.
+pub fn analysis_bands(settings: &TempoExtractionSettings, sr: f64) -> ContainerRM<f64, Dynamic, U2> {
+	settings.analysis_band_bins().as_ref().map(|c| c.clone_owned()).unwrap_or_else(|| {
+		let ret = crate::default_audio_bands(sr);
+		ret.transmute_dims(D!(ret.row_count()), ret.col_dim(), ret.row_stride_dim(), ret.col_stride_dim()).owned()
+	})
+}
 
-	if *settings.verbose() { println!(" - Calculating tempogram") }
+pub fn analyze_tempogram(novelty_curve: &RowVec<f64, Dynamic>, nc_sr: f64, settings: &TempoExtractionSettings, observer: &dyn ProgressObserver)
+	-> Result<TempogramAnalysis, Cancelled>
+{
+	enter_stage(observer, Stage::Tempogram)?;
+	let _span = crate::diagnostics::timed(tracing::info_span!("tempogram_stage"));
+	let bpms = settings.constraints().narrow_scan_bpms(settings.scan_bpms());
 	// Make Tempogram
 	let (mut tempogram, tempogram_sr) = crate::novelty_curve_to_tempogram_dft(
-		&novelty_curve,
+		novelty_curve,
 		nc_sr,
 		D!(settings.get_tempo_window(nc_sr)),
 		D!(settings.get_tempo_hop_size(nc_sr)),
-		settings.scan_bpms()
+		&bpms
 	);
 	// Normalize tempogram
 	normalize_cols_inplace(&mut tempogram, |s| norm_p2_c(s));
 	let tempogram_mag = (&tempogram).norm();
 	let mut tempogram_mag_t = ContainerRM::zeros(tempogram_mag.row_dim(), tempogram_mag.col_dim());
 	tempogram_mag_t.copy_from(&tempogram_mag);
+	exit_stage(observer, Stage::Tempogram)?;
 
-	if *settings.verbose() { println!(" - Calculating cyclic tempogram") }
+	enter_stage(observer, Stage::CyclicTempogram)?;
 	// Make Cyclic Tempogram
+	let cyclic_settings = settings.constraints().narrow_cyclic_settings(settings.cyclic_tempogram_settings(), &bpms, *settings.ref_tempo());
 	let (cyclic_tempogram, cyclic_tempogram_axis)
-		= crate::tempogram_to_cyclic_tempogram(&tempogram, settings.scan_bpms(), D!(*settings.octave_divider()), *settings.ref_tempo());
+		= crate::tempogram_to_cyclic_tempogram_custom(
+			&tempogram,
+			&bpms,
+			D!(*settings.octave_divider()),
+			*settings.ref_tempo(),
+			&cyclic_settings
+		);
+	exit_stage(observer, Stage::CyclicTempogram)?;
 
-	if *settings.verbose() { println!(" - Preprocessing and cleaning tempogram") }
+	enter_stage(observer, Stage::Preprocessing)?;
 	// Preprocess tempogram
-	let triplet_corrected_cyclic_tempogram = crate::include_triplets(&cyclic_tempogram, &cyclic_tempogram_axis, *settings.triplet_weight());
-	let mut smooth_tempogram = crate::smoothen_tempogram(
+	let mut metrical_ratios = vec![(3. / 2., *settings.triplet_weight())];
+	metrical_ratios.extend(settings.metrical_ratios().iter().cloned());
+	if *settings.estimate_metrical_ratio() {
+		metrical_ratios = crate::estimate_dominant_ratio(&cyclic_tempogram, &cyclic_tempogram_axis, &metrical_ratios, true)
+			.into_iter().collect();
+		tracing::info!(ratio = ?metrical_ratios.first(), "Estimated dominant metrical ratio");
+	}
+	let triplet_corrected_cyclic_tempogram = crate::reinforce_metrical_ratios(&cyclic_tempogram, &cyclic_tempogram_axis, &metrical_ratios, true);
+	let mut smooth_tempogram = crate::smoothen_tempogram_custom(
 		&triplet_corrected_cyclic_tempogram,
-		D!(settings.get_smooth_length(tempogram_sr))
+		D!(settings.get_smooth_length(tempogram_sr)),
+		*settings.smoothing_filter()
 	);
 	smooth_tempogram.as_iter_mut().for_each(|v| if *v < 0. { *v = 0.; } else {});
+	exit_stage(observer, Stage::Preprocessing)?;
 
-	if *settings.verbose() { println!(" - Tempo peaks extraction") }
+	enter_stage(observer, Stage::TempoCurve)?;
 	// Tempo curve extraction
 	let tempo_curve = crate::extract_tempo_curve(&smooth_tempogram, &cyclic_tempogram_axis);
 	let tempo_curve = crate::correct_curve_by_length(&tempo_curve, settings.get_min_section_length(tempogram_sr));
+	exit_stage(observer, Stage::TempoCurve)?;
+
+	Ok(TempogramAnalysis {
+		bpms,
+		tempogram,
+		tempogram_mag: tempogram_mag_t,
+		sr: tempogram_sr,
+		cyclic_tempogram,
+		cyclic_tempogram_axis,
+		smooth_tempogram,
+		tempo_curve,
+	})
+}
+
+pub fn extract_tempo_sections(novelty_curve: &RowVec<f64, Dynamic>, nc_sr: f64, analysis: &TempogramAnalysis, settings: &TempoExtractionSettings, observer: &dyn ProgressObserver)
+	-> Result<Vec<TempoSection>, Cancelled>
+{
+	extract_tempo_sections_detailed(novelty_curve, nc_sr, analysis, settings, observer).map(|(sections, _)| sections)
+}
+
+/// Tempo sections with the offset scores searched for each section. Respects the constraints in the settings.
+pub fn extract_tempo_sections_detailed(novelty_curve: &RowVec<f64, Dynamic>, nc_sr: f64, analysis: &TempogramAnalysis, settings: &TempoExtractionSettings, observer: &dyn ProgressObserver)
+	-> Result<(Vec<TempoSection>, Vec<crate::OffsetLandscape>), Cancelled>
+{
+	let _span = crate::diagnostics::timed(tracing::info_span!("sections_stage"));
+	let constraints = settings.constraints();
+	let tempo_sections = if constraints.fixed_bpm().is_some() {
+		fixed_bpm_sections(novelty_curve, nc_sr, settings)
+	} else if !constraints.sections().is_empty() {
+		supplied_sections(analysis, settings, observer)?
+	} else {
+		let mut ret = discover_tempo_sections(analysis, settings, observer)?;
+		ret.iter_mut().for_each(|s| correct_bpm_height(s, settings));
+		ret
+	};
 
-	let tempo_segments = crate::split_curve(&tempo_curve);
-	let tempo_sections = crate::tempo_segments_to_sections(&tempo_curve, &tempo_segments, tempogram_sr, *settings.ref_tempo());
+	refine_tempo_sections(novelty_curve, nc_sr, tempo_sections, settings, observer)
+}
+
+/// Sections from the tempo curve split by length and optionally snapped to tempo changes.
+pub fn discover_tempo_sections(analysis: &TempogramAnalysis, settings: &TempoExtractionSettings, observer: &dyn ProgressObserver)
+	-> Result<Vec<TempoSection>, Cancelled>
+{
+	let tempogram_sr = analysis.sr;
+	let tempo_curve = &analysis.tempo_curve;
+
+	let tempo_segments = crate::split_curve(tempo_curve);
+	let tempo_sections = crate::tempo_segments_to_sections(tempo_curve, &tempo_segments, tempogram_sr, *settings.ref_tempo());
 	let tempo_sections_tmp = crate::merge_sections(&tempo_sections, *settings.bpm_merge_threshold());
 
 	let mut tempo_sections = Vec::new();
@@ -159,110 +511,133 @@ pub fn extract_tempo<L, P, S>(a: &S, settings: &TempoExtractionSettings) -> Vec<
 		crate::split_section(s, &mut tempo_sections, *settings.max_section_length());
 	}
 
-	if *settings.verbose() { println!(" - Tempo offset estimation") }
-	// Correct bpm height
-	for s in tempo_sections.iter_mut() {
-		let best_multiple = settings.tempo_multiples().iter().cloned()
-			.max_by(|a, b| (settings.preferred_bpm() - a * s.bpm()).partial_cmp(&(settings.preferred_bpm() - b * s.bpm())).unwrap_or(Equal));
-		s.set_bpm(best_multiple.unwrap_or(1.) * s.bpm());
-		s.set_bpm((s.bpm() / settings.bpm_rounding_precision()).round() * settings.bpm_rounding_precision());
+	if let Some(snap_distance) = settings.boundary_snap_distance() {
+		snap_boundaries(&mut tempo_sections, analysis, settings, *snap_distance, observer)?;
+	}
+	Ok(tempo_sections)
+}
+
+/// Estimates the bpm offset of every section. The bpm is searched within the doubt window unless locked.
+pub fn refine_tempo_sections(novelty_curve: &RowVec<f64, Dynamic>, nc_sr: f64, mut tempo_sections: Vec<TempoSection>, settings: &TempoExtractionSettings, observer: &dyn ProgressObserver)
+	-> Result<(Vec<TempoSection>, Vec<crate::OffsetLandscape>), Cancelled>
+{
+	let constraints = settings.constraints();
+	let doubt_window = if constraints.bpm_locked() { 0. } else { *settings.bpm_doubt_window() };
+
+	enter_stage(observer, Stage::OffsetEstimation)?;
+	let section_count = tempo_sections.len();
+	let mut offset_landscapes = Vec::with_capacity(section_count);
+	for (i, s) in tempo_sections.iter_mut().enumerate() {
+		if !constraints.bpm_locked() { s.set_bpm(constraints.fit_bpm(s.bpm())); }
 
 		// Correct offset
-		crate::extract_offset(&novelty_curve, nc_sr, s, settings.tempo_multiples(), *settings.bpm_doubt_window(), *settings.bpm_doubt_step());
-		crate::correct_offset(s, *settings.smallest_fraction_shift());
+		let mut refined = s.clone();
+		offset_landscapes.push(crate::extract_offset_custom(novelty_curve, nc_sr, &mut refined, settings.tempo_multiples(), doubt_window, *settings.bpm_doubt_step(), observer)?);
+		crate::correct_offset(&mut refined, *settings.smallest_fraction_shift());
+		if !constraints.bpm_locked() { s.set_bpm(constraints.fit_bpm(refined.bpm())); }
+		if !constraints.offset_locked() { s.set_offset(refined.offset()); }
+
+		crate::check_cancelled(observer)?;
+		observer.stage_progress(Stage::OffsetEstimation, (i + 1) as f64 / section_count as f64);
 	}
+	exit_stage(observer, Stage::OffsetEstimation)?;
 
-	if *settings.verbose() { println!(" - Done!") }
+	if tempo_sections.is_empty() { tracing::warn!("No tempo sections found"); }
+	tracing::info!(sections = tempo_sections.len(), global_bpm = ?crate::global_bpm(&tempo_sections), "Extracted tempo sections");
+	Ok((tempo_sections, offset_landscapes))
+}
 
-	// Save a click track
-	if *settings.save_click_track() {
-		let path = settings.save_path().join("click_track.mp3");
-		let mut click_audio = AudioDeinterleaved::new(DeinterleavedStorage::zeros(a.channel_dim(), a.sample_dim()), a.sample_rate());
-		click_audio.as_iter_mut().zip(a.as_iter()).for_each(|(o, i)| *o = *i as f32);
-		crate::save_tempo_click_track(&path, click_audio, &tempo_sections, *settings.click_fraction()).unwrap();
-	}
+/// Moves the bpm to the tempo multiple closest to the preferred bpm and rounds it.
+pub fn correct_bpm_height(s: &mut TempoSection, settings: &TempoExtractionSettings) {
+	let best_multiple = settings.tempo_multiples().iter().cloned()
+		.max_by(|a, b| (settings.preferred_bpm() - a * s.bpm()).partial_cmp(&(settings.preferred_bpm() - b * s.bpm())).unwrap_or(Equal));
+	tracing::debug!(start = s.start(), bpm = s.bpm(), multiple = ?best_multiple, "Corrected bpm height");
+	s.set_bpm(best_multiple.unwrap_or(1.) * s.bpm());
+	s.set_bpm((s.bpm() / settings.bpm_rounding_precision()).round() * settings.bpm_rounding_precision());
+}
 
-	// Plot data
-	if *settings.visualize() {
-		let audio_x = litdsp::wave::calculate_time(a.col_dim(), sr);
-		let plot = Plot::new("audio")
-			.add_chart(
-				LineBuilder::default()
-					.identifier("audio")
-					.data(XYData::new(
-						provider_litcontainer(Fetch::Remote, &audio_x, None).unwrap(),
-						provider_litcontainer(Fetch::Remote, a, None).unwrap(),
-					))
-					.name("Audio Wave")
-					.build()
-					.unwrap()
-			)
-			.add_chart(
-				LineBuilder::default()
-					.identifier("chart_1")
-					.data(XYData::new(
-						provider_litcontainer(Fetch::Remote, &litdsp::wave::calculate_time(novelty_curve.col_dim(), nc_sr), Some("chart_1_x".into())).unwrap(),
-						provider_litcontainer(Fetch::Remote, &(&novelty_curve / novelty_curve.maximum()), Some("chart_1_y".into())).unwrap(),
-					))
-					.name("Novelty Curve")
-					.build()
-					.unwrap()
-			);
+/// Supplied sections or a single section covering the novelty curve, all with the fixed bpm.
+fn fixed_bpm_sections(novelty_curve: &RowVec<f64, Dynamic>, nc_sr: f64, settings: &TempoExtractionSettings) -> Vec<TempoSection> {
+	let constraints = settings.constraints();
+	let bpm = constraints.fixed_bpm().unwrap_or(0.);
+	if constraints.sections().is_empty() {
+		vec![TempoSection::new(0., (novelty_curve.col_count() as f64 / nc_sr) as f32, bpm, 0.)]
+	} else {
+		constraints.sections().iter().map(|s| TempoSection::new(s.start(), s.end(), bpm, s.offset())).collect()
+	}
+}
 
-		let plot2 = Plot::new("tempogram")
-			.add_chart(
-				HeatmapBuilder::default()
-					.data(XYZData::new(
-						provider_litcontainer(Fetch::Remote, &litdsp::wave::calculate_time(tempogram.col_dim(), tempogram_sr), None).unwrap(),
-						provider_litcontainer(Fetch::Remote, settings.scan_bpms(), None).unwrap(),
-						provider_litcontainer(Fetch::Remote, &tempogram_mag_t, None).unwrap(),
-					))
-					.name("Tempogram")
-					.build().unwrap()
-			);
+/// Supplied sections with missing bpms estimated from the tempo curve and boundaries snapped to tempo changes.
+fn supplied_sections(analysis: &TempogramAnalysis, settings: &TempoExtractionSettings, observer: &dyn ProgressObserver)
+	-> Result<Vec<TempoSection>, Cancelled>
+{
+	let constraints = settings.constraints();
+	let mut ret = constraints.sections().clone();
+	if !constraints.boundaries_locked() {
+		let snap_distance = settings.boundary_snap_distance().unwrap_or(*settings.boundary_kernel_length());
+		snap_boundaries(&mut ret, analysis, settings, snap_distance, observer)?;
+	}
 
-		let plot3 = Plot::new("tempogram_cyclic")
-			.add_chart(
-				HeatmapBuilder::default()
-					.data(XYZData::new(
-						provider_litcontainer(Fetch::Remote, &litdsp::wave::calculate_time(tempogram.col_dim(), tempogram_sr), None).unwrap(),
-						provider_litcontainer(Fetch::Remote, &cyclic_tempogram_axis, None).unwrap(),
-						provider_litcontainer(Fetch::Remote, &cyclic_tempogram, None).unwrap(),
-					))
-					.name("Cyclic Tempogram")
-					.build().unwrap()
-			);
+	let tempo_curve = &analysis.tempo_curve;
+	for s in ret.iter_mut().filter(|s| !(s.bpm() > 0.)) {
+		let start = ((s.start() as f64 * analysis.sr) as usize).min(tempo_curve.col_count());
+		let end = ((s.end() as f64 * analysis.sr) as usize).min(tempo_curve.col_count());
+		if end <= start { continue; }
+		let curve = tempo_curve.slice_cols(start..end);
+		s.set_bpm((curve.as_iter().sum::<f64>() / (end - start) as f64 * *settings.ref_tempo()) as f32);
+		correct_bpm_height(s, settings);
+	}
+	Ok(ret)
+}
 
-		let plot4 = Plot::new("smooth_tempogram")
-			.add_chart(
-				HeatmapBuilder::default()
-					.data(XYZData::new(
-						provider_litcontainer(Fetch::Remote, &litdsp::wave::calculate_time(tempogram.col_dim(), tempogram_sr), None).unwrap(),
-						provider_litcontainer(Fetch::Remote, &cyclic_tempogram_axis, None).unwrap(),
-						provider_litcontainer(Fetch::Remote, &smooth_tempogram, None).unwrap(),
-					))
-					.name("Smooth Tempogram")
-					.build().unwrap()
-			)
-			.add_chart(
-				LineBuilder::default()
-					.data(XYData::new(
-						provider_litcontainer(Fetch::Remote, &litdsp::wave::calculate_time(tempogram.col_dim(), tempogram_sr), None).unwrap(),
-						provider_litcontainer(Fetch::Remote, &tempo_curve, None).unwrap()
-					))
-					.name("Tempo Curve")
-					.build().unwrap()
-			);
+fn snap_boundaries(sections: &mut Vec<TempoSection>, analysis: &TempogramAnalysis, settings: &TempoExtractionSettings, snap_distance: f32, observer: &dyn ProgressObserver)
+	-> Result<(), Cancelled>
+{
+	enter_stage(observer, Stage::Boundaries)?;
+	let boundaries = crate::detect_tempo_boundaries(
+		&analysis.cyclic_tempogram,
+		analysis.sr,
+		settings.get_boundary_kernel_length(analysis.sr),
+		*settings.boundary_method(),
+		0.
+	);
+	crate::snap_section_boundaries(sections, &boundaries, snap_distance, *settings.min_section_length());
+	exit_stage(observer, Stage::Boundaries)
+}
 
-		let report = Report::new("Novelty Curve")
-			.add_node(plot)
-			.add_node(plot2)
-			.add_node(plot3)
-			.add_node(plot4);
+/// Adds section boundaries with bpm labels, offsets and the beat grid between min and max.
+fn add_result_overlay(mut plot: Plot, sections: &Vec<TempoSection>, min: f64, max: f64) -> Plot {
+	for (i, s) in sections.iter().enumerate() {
+		plot = plot
+			.add_chart(vertical_line(&format!("section_{}", i), s.start() as f64, min, max, &format!("{:.2} BPM", s.bpm())))
+			.add_chart(vertical_line(&format!("offset_{}", i), s.offset() as f64, min, max * 0.5, &format!("Offset {:.3}s", s.offset())));
+	}
 
-		let path = settings.save_path().join("plot");
-		report.force_save(path.as_path()).unwrap();
+	// Beat grid as spikes rising from the bottom
+	let (mut x, mut y) = (Vec::new(), Vec::new());
+	for b in crate::beat_times(sections) {
+		x.extend_from_slice(&[b as f64; 3]);
+		y.extend_from_slice(&[min, min + (max - min) * 0.2, min]);
 	}
+	plot.add_chart(
+		LineBuilder::default()
+			.identifier("beat_grid")
+			.data(XYData::new(
+				provider_litcontainer(Fetch::Remote, &ContainerRM::from_vec(U1, D!(x.len()), &x), Some("beat_grid_x".into())).unwrap(),
+				provider_litcontainer(Fetch::Remote, &ContainerRM::from_vec(U1, D!(y.len()), &y), Some("beat_grid_y".into())).unwrap(),
+			))
+			.name("Beat Grid")
+			.build().unwrap()
+	)
+}
 
-	tempo_sections
-}
\ No newline at end of file
+fn vertical_line(id: &str, t: f64, min: f64, max: f64, name: &str) -> Line {
+	LineBuilder::default()
+		.identifier(id)
+		.data(XYData::new(
+			provider_litcontainer(Fetch::Remote, &ContainerRM::from_vec(U1, U2, &[t, t]), Some(format!("{}_x", id))).unwrap(),
+			provider_litcontainer(Fetch::Remote, &ContainerRM::from_vec(U1, U2, &[min, max]), Some(format!("{}_y", id))).unwrap(),
+		))
+		.name(name)
+		.build().unwrap()
+}
@@ -1,5 +1,6 @@
 use litcontainers::*;
 use crate::Segment;
+use std::cmp::Ordering::Equal;
 
 #[derive(Debug, Clone)]
 pub struct TempoSection {
@@ -14,8 +15,12 @@ impl TempoSection {
 
 	pub fn start(&self) -> f32 { self.start }
 
+	pub fn set_start(&mut self, v: f32) { self.start = v }
+
 	pub fn end(&self) -> f32 { self.end }
 
+	pub fn set_end(&mut self, v: f32) { self.end = v }
+
 	pub fn bpm(&self) -> f32 { self.bpm }
 
 	pub fn set_bpm(&mut self, v: f32) { self.bpm = v }
@@ -86,4 +91,22 @@ pub fn split_section(s: TempoSection, sections: &mut Vec<TempoSection>, max_dura
 	let mut s2 = s.clone();
 	s2.start = s.start + duration / 2.;
 	split_section(s2, sections, max_duration);
-}
\ No newline at end of file
+}
+
+/// Total duration per tempo sorted by duration. Tempi within half a BPM are grouped under the first one.
+pub fn tempo_durations(sections: &[TempoSection]) -> Vec<(f32, f32)> {
+	let mut ret: Vec<(f32, f32)> = Vec::new();
+	for s in sections {
+		match ret.iter_mut().find(|(bpm, _)| (*bpm - s.bpm).abs() < 0.5) {
+			Some((_, duration)) => *duration += s.duration(),
+			None => ret.push((s.bpm, s.duration())),
+		}
+	}
+	ret.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap_or(Equal));
+	ret
+}
+
+/// BPM covering the largest total duration of the piece.
+pub fn global_bpm(sections: &[TempoSection]) -> Option<f32> {
+	tempo_durations(sections).first().map(|(bpm, _)| *bpm)
+}
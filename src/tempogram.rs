@@ -10,6 +10,7 @@ pub fn novelty_curve_to_tempogram_dft<C, S, W, H, F>(s: &S, sr: f64, window_dim:
 	      <C as DimAdd<Dynamic>>::Output: DimAdd<Dynamic>,
 	      H: Dim, F: Dim
 {
+	let _span = crate::diagnostics::timed(tracing::debug_span!("novelty_curve_to_tempogram_dft", sr, window = window_dim.value(), hop = hop_dim.value(), bpms = bpms.col_count()));
 	let w = window::hanning(window_dim);
 	let window_length = window_dim.value();
 	let window_length_half = (window_length as f32 / 2.).round() as usize;
@@ -25,45 +26,184 @@ pub fn novelty_curve_to_tempogram_dft<C, S, W, H, F>(s: &S, sr: f64, window_dim:
 	(tg, sr)
 }
 
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Interpolation {
+	Nearest,
+	Linear,
+	Cubic,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum OctavePooling {
+	Mean,
+	Max,
+}
+
+#[derive(Debug, Clone, Builder, Getters)]
+#[builder(build_fn(validate = "Self::validate"))]
+pub struct CyclicTempogramSettings {
+	/// Interpolation used to map the tempogram onto the log bpm grid.
+	#[builder(default = "Interpolation::Nearest")]
+	interpolation: Interpolation,
+	/// Octave range (log2 of bpm / ref_tempo) to fold. End is exclusive. Covers all bpms including partial octaves if None.
+	#[builder(default = "None")]
+	octave_range: Option<(i32, i32)>,
+	/// Weight for each octave starting at the lowest one. Missing weights default to 1.
+	#[builder(default = "Vec::new()")]
+	octave_weights: Vec<f64>,
+	/// How the octaves are pooled into a single cyclic bin.
+	#[builder(default = "OctavePooling::Mean")]
+	pooling: OctavePooling,
+}
+
+impl Default for CyclicTempogramSettings {
+	fn default() -> Self { CyclicTempogramSettingsBuilder::default().build().unwrap() }
+}
+
+impl CyclicTempogramSettingsBuilder {
+	fn validate(&self) -> Result<(), String> {
+		match self.octave_range {
+			Some(Some((start, end))) if start >= end => Err(format!("Octave range {}:{} contains no octave", start, end)),
+			_ => Ok(()),
+		}
+	}
+}
+
 pub fn tempogram_to_cyclic_tempogram<C, F, O>(tg: &ContainerRM<c64, F, C>, bpms: &RowVec<f64, F>, octave_divider: O, ref_tempo: f64)
 	-> (ContainerRM<f64, O, C>, RowVec<f64, O>)
 	where F: Dim, C: Dim, O: Dim
 {
+	tempogram_to_cyclic_tempogram_custom(tg, bpms, octave_divider, ref_tempo, &CyclicTempogramSettings::default())
+}
+
+pub fn tempogram_to_cyclic_tempogram_custom<C, F, O>(tg: &ContainerRM<c64, F, C>, bpms: &RowVec<f64, F>, octave_divider: O, ref_tempo: f64, settings: &CyclicTempogramSettings)
+	-> (ContainerRM<f64, O, C>, RowVec<f64, O>)
+	where F: Dim, C: Dim, O: Dim
+{
+	let _span = crate::diagnostics::timed(tracing::debug_span!("tempogram_to_cyclic_tempogram", octave_divider = octave_divider.value(), ref_tempo));
 	let min_bpm = bpms.minimum();
 	let max_bpm = bpms.maximum();
-	let ref_octave = ref_tempo / min_bpm;
-	let min_octave = (min_bpm / ref_tempo).log2().round();
-	let max_octave = (max_bpm / ref_tempo).log2().round() + 1.;
+	let (min_octave, max_octave) = match settings.octave_range {
+		Some((start, end)) => (start as f64, end as f64),
+		None => {
+			let (start, end) = octave_range_covering(bpms, ref_tempo);
+			(start as f64, end as f64)
+		},
+	};
+	let step = 1. / octave_divider.value() as f64;
 
 	let mag_tempogram = tg.norm();
 
-	let log_bpm_count = ((max_octave - 1. / octave_divider.value() as f64) - min_octave) / (1. / octave_divider.value() as f64);
-	let log_bpm = RowVec::regspace_step_rows(
-		U1,
-		D!(log_bpm_count.floor() as usize),
-		min_octave as f64,
-		1. / octave_divider.value() as f64
-	).exp2() * ref_tempo;
-	let mut log_tempogram = ContainerRM::zeros(log_bpm.col_dim(), mag_tempogram.col_dim());
-	interp1_nearest_cols(&bpms.t(), &mag_tempogram, &log_bpm.t(), &mut log_tempogram);
+	let log_bpm_count = ((max_octave - min_octave).max(0.) as usize) * octave_divider.value();
+	let log_bpm = RowVec::regspace_step_rows(U1, D!(log_bpm_count), min_octave, step).exp2() * ref_tempo;
+	let log_tempogram = interp1_rows(bpms, &mag_tempogram, &log_bpm, settings.interpolation);
+
+	// Only the bins within the scanned bpm range carry information
+	let start_pos = log_bpm.as_iter().position(|v| *v >= min_bpm).unwrap_or(log_bpm_count);
+	let end_pos = log_bpm.as_iter().rposition(|v| *v < max_bpm).unwrap_or(0);
 
 	let mut cyclic_tempogram = ContainerRM::zeros(octave_divider, mag_tempogram.col_dim());
-	let end_pos = log_bpm.as_iter().cloned().enumerate().filter(|(_, v)| *v < max_bpm).last().unwrap().0;
 	for (i, mut row) in cyclic_tempogram.as_row_slice_mut_iter().enumerate() {
-		let range = (i..end_pos).step_by(octave_divider.value());
-		let range_size = range.len();
-		for j in range {
-			row += &log_tempogram.slice_rows(j);
+		let (mut weight_sum, mut count) = (0., 0);
+		for j in (i..end_pos).step_by(octave_divider.value()).filter(|j| *j >= start_pos) {
+			let weight = settings.octave_weights.get(j / octave_divider.value()).cloned().unwrap_or(1.);
+			let octave_row = log_tempogram.slice_rows(j) * weight;
+			match settings.pooling {
+				OctavePooling::Mean => row += &octave_row,
+				OctavePooling::Max => {
+					row.as_iter_mut().zip(octave_row.as_iter())
+						.for_each(|(o, v)| if count == 0 || *v > *o { *o = *v });
+				}
+			}
+			weight_sum += weight;
+			count += 1;
+		}
+		if settings.pooling == OctavePooling::Mean && weight_sum > 0. {
+			row /= weight_sum;
 		}
-		row /= range_size as f64;
 	}
 
-	let y_axis = log_bpm.slice_cols(SizedRange::new(0, octave_divider)) * (ref_octave / ref_tempo);
+	let y_axis = log_bpm.slice_cols(SizedRange::new(0, octave_divider)) * (1. / (ref_tempo * min_octave.exp2()));
 	(cyclic_tempogram, y_axis)
 }
 
-pub fn bpm_to_cyclic(bpm: f64, ref_tempo: f64) -> f64 {
-	let mins = (bpm / 60.).log2().floor();
-	let diff = (ref_tempo * 2f64.powf(mins + 1.)) -  (ref_tempo * 2f64.powf(mins));
-	bpm - (60. * 2f64.powf(mins)) / diff + 1.
-}
\ No newline at end of file
+/// Octave range (as used by `CyclicTempogramSettings`) which covers all of the given bpms including partial octaves.
+pub fn octave_range_covering<F: Dim>(bpms: &RowVec<f64, F>, ref_tempo: f64) -> (i32, i32) {
+	let start = (bpms.minimum() / ref_tempo).log2().floor() as i32;
+	let end = (bpms.maximum() / ref_tempo).log2().ceil() as i32;
+	(start, end.max(start + 1))
+}
+
+/// Interpolates rows of `s` positioned at `x` onto positions `xi`. Values outside of `x` are clamped to the edges.
+pub fn interp1_rows<R, C, RI, S>(x: &RowVec<f64, R>, s: &S, xi: &RowVec<f64, RI>, interpolation: Interpolation)
+	-> ContainerRM<f64, RI, C>
+	where R: Dim, C: Dim, RI: Dim, S: Storage<f64, R, C>
+{
+	let mut ret = ContainerRM::zeros(xi.col_dim(), s.col_dim());
+	if interpolation == Interpolation::Nearest {
+		interp1_nearest_cols(&x.t(), s, &xi.t(), &mut ret);
+		return ret;
+	}
+
+	let n = x.col_count();
+	if n == 0 { return ret; }
+
+	for (k, mut row) in ret.as_row_slice_mut_iter().enumerate() {
+		let v = xi[k];
+		if n == 1 || v <= x[0] || v >= x[n - 1] {
+			let edge = if n == 1 || v <= x[0] { 0 } else { n - 1 };
+			row.copy_from(&s.slice_rows(edge));
+			continue;
+		}
+
+		// Segment j..j+1 containing v
+		let j = (1..n).find(|i| x[*i] > v).unwrap_or(n - 1) - 1;
+		let h = x[j + 1] - x[j];
+		let t = (v - x[j]) / h;
+		let weights = match interpolation {
+			Interpolation::Cubic => {
+				// Cubic hermite spline with finite difference tangents
+				let (t2, t3) = (t * t, t * t * t);
+				let (h00, h10, h01, h11) = (2. * t3 - 3. * t2 + 1., t3 - 2. * t2 + t, -2. * t3 + 3. * t2, t3 - t2);
+				let (jp, jn) = (j.max(1) - 1, (j + 2).min(n - 1));
+				let m0 = h10 * h / (x[j + 1] - x[jp]);
+				let m1 = h11 * h / (x[jn] - x[j]);
+				vec![(j, h00), (j + 1, h01), (j + 1, m0), (jp, -m0), (jn, m1), (j, -m1)]
+			},
+			_ => vec![(j, 1. - t), (j + 1, t)],
+		};
+
+		for (src, w) in weights {
+			row += &(s.slice_rows(src) * w);
+		}
+	}
+
+	ret
+}
+
+/// Maps bpm onto the cyclic tempo axis. Result lies within [1, 2) where 1 corresponds to ref_tempo and its octaves.
+/// None if either tempo is not a positive number.
+pub fn bpm_to_cyclic(bpm: f64, ref_tempo: f64) -> Option<f64> {
+	if bpm.is_nan() || ref_tempo.is_nan() || bpm <= 0. || ref_tempo <= 0. { return None; }
+	let octave = (bpm / ref_tempo).log2();
+	let frac = octave - octave.floor();
+	// Values an octave apart up to rounding errors map to 1 instead of 2
+	Some(if frac > 1. - 1e-12 { 1. } else { frac.exp2() })
+}
+
+/// Lists every octave equivalent bpm of a cyclic tempo within the inclusive bpm range in ascending order.
+pub fn cyclic_to_bpm_candidates(cyclic: f64, ref_tempo: f64, bpm_range: (f64, f64)) -> Vec<f64> {
+	let (min_bpm, max_bpm) = bpm_range;
+	if cyclic <= 0. || min_bpm > max_bpm { return Vec::new(); }
+
+	let base = match bpm_to_cyclic(cyclic * ref_tempo, ref_tempo) {
+		Some(v) => v * ref_tempo,
+		None => return Vec::new(),
+	};
+	let min_octave = (min_bpm.max(std::f64::MIN_POSITIVE) / base).log2().floor() as i32;
+	let max_octave = (max_bpm / base).log2().ceil() as i32;
+	(min_octave..=max_octave)
+		.map(|o| base * 2f64.powi(o))
+		.filter(|bpm| *bpm >= min_bpm && *bpm <= max_bpm)
+		.collect()
+}
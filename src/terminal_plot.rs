@@ -0,0 +1,97 @@
+use litcontainers::*;
+use crate::{TempoSection, TempogramAnalysis};
+
+const GUTTER: usize = 9;
+const SPARKS: [char; 8] = ['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+const SHADES: [char; 5] = [' ', '░', '▒', '▓', '█'];
+
+/// Novelty curve sparkline, cyclic tempogram heatmap and section boundaries with bpm labels along a time ruler.
+/// Width is the total amount of characters per line, height the amount of heatmap rows.
+/// The heatmap is left out without an analysis.
+pub fn render_terminal_plot(
+	novelty_curve: &RowVec<f64, Dynamic>, nc_sr: f64, analysis: Option<&TempogramAnalysis>, sections: &Vec<TempoSection>,
+	width: usize, height: usize
+) -> String {
+	let columns = width.saturating_sub(GUTTER).max(10);
+	let duration = (novelty_curve.col_count() as f64 / nc_sr).max(1e-9);
+	let column = |t: f64| (((t / duration) * columns as f64) as usize).min(columns - 1);
+	let mut ret = String::new();
+
+	// Section boundaries with their bpm
+	let mut labels = vec![' '; columns];
+	let mut boundaries = vec![' '; columns];
+	for s in sections {
+		let c = column(s.start() as f64);
+		boundaries[c] = '|';
+		write_label(&mut labels, c, &format!("{:.1}", s.bpm()));
+	}
+	ret.push_str(&line("BPM", &labels));
+	ret.push_str(&line("", &boundaries));
+
+	// Novelty curve sparkline
+	let novelty = reduce_max(&novelty_curve.as_iter().cloned().collect(), columns);
+	let max = novelty.iter().cloned().fold(1e-9, f64::max);
+	let sparkline: Vec<char> = novelty.iter()
+		.map(|v| SPARKS[((v.max(0.) / max) * (SPARKS.len() - 1) as f64).round() as usize])
+		.collect();
+	ret.push_str(&line("Novelty", &sparkline));
+
+	// Cyclic tempogram with the highest tempo on top
+	if let Some(analysis) = analysis {
+		let rows: Vec<Vec<f64>> = analysis.cyclic_tempogram().as_row_slice_iter()
+			.map(|r| reduce_max(&r.as_iter().cloned().collect(), columns))
+			.collect();
+		let axis = analysis.cyclic_tempogram_axis();
+		let height = height.max(1).min(rows.len().max(1));
+		let max = rows.iter().flat_map(|r| r.iter()).cloned().fold(1e-9, f64::max);
+		for h in (0..height).rev() {
+			let (r0, r1) = (h * rows.len() / height, ((h + 1) * rows.len() / height).max(h * rows.len() / height + 1).min(rows.len()));
+			if r0 >= rows.len() { continue; }
+			let shades: Vec<char> = (0..columns).map(|c| {
+				let v = rows[r0..r1].iter().map(|r| r[c]).fold(0., f64::max);
+				SHADES[((v.max(0.) / max) * (SHADES.len() - 1) as f64).round() as usize]
+			}).collect();
+			let label = if h == height - 1 || h == 0 { format!("{:.2}", axis[r0]) } else { String::new() };
+			ret.push_str(&line(&label, &shades));
+		}
+	}
+
+	// Time ruler
+	let step = [1., 2., 5., 10., 15., 30., 60., 120., 300.].iter().cloned()
+		.find(|step| duration / step * 8. <= columns as f64)
+		.unwrap_or(600.);
+	let mut ticks = vec!['─'; columns];
+	let mut times = vec![' '; columns];
+	let mut t = 0.;
+	while t < duration {
+		let c = column(t);
+		ticks[c] = '┴';
+		write_label(&mut times, c, &format!("{}s", t));
+		t += step;
+	}
+	ret.push_str(&line("", &ticks));
+	ret.push_str(&line("Time", &times));
+	ret
+}
+
+fn line(label: &str, content: &[char]) -> String {
+	format!("{:>width$} {}\n", label, content.iter().collect::<String>(), width = GUTTER - 1)
+}
+
+/// Writes the label starting at the column if it does not overwrite another label.
+fn write_label(line: &mut Vec<char>, column: usize, label: &str) {
+	let end = column + label.chars().count();
+	if end > line.len() || line[column.saturating_sub(1)..end].iter().any(|c| *c != ' ') { return; }
+	for (i, c) in label.chars().enumerate() {
+		line[column + i] = c;
+	}
+}
+
+/// Maximum of every of the given amount of equally sized chunks.
+fn reduce_max(values: &Vec<f64>, count: usize) -> Vec<f64> {
+	(0..count).map(|i| {
+		let start = i * values.len() / count;
+		let end = ((i + 1) * values.len() / count).max(start + 1).min(values.len());
+		if start >= values.len() { 0. } else { values[start..end].iter().cloned().fold(std::f64::MIN, f64::max) }
+	}).collect()
+}
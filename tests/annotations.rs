@@ -0,0 +1,75 @@
+use littempo::{TempoSection, TempoAnnotation};
+
+#[test]
+fn jams_round_trip() {
+	let sections = vec![
+		TempoSection::new(0., 10., 120., 0.25),
+		TempoSection::new(10., 20., 100., 10.3),
+	];
+	let annotation = TempoAnnotation::from_sections(sections.clone());
+	let result = littempo::parse_jams(&littempo::write_jams(&annotation)).unwrap();
+
+	assert_eq!(result.beats().len(), annotation.beats().len());
+	for (target, s) in sections.iter().zip(result.sections().iter()) {
+		assert!((target.start() - s.start()).abs() < 0.0001);
+		assert!((target.end() - s.end()).abs() < 0.0001);
+		assert!((target.bpm() - s.bpm()).abs() < 0.0001);
+		assert!((target.offset() - s.offset()).abs() < 0.0001);
+	}
+}
+
+#[test]
+fn jams_dense_layout() {
+	let data = r#"{"annotations": [
+		{"namespace": "tempo", "data": {"time": [0.0], "duration": [30.0], "value": [92.5], "confidence": [1.0]}},
+		{"namespace": "beat", "data": {"time": [0.4, 1.05], "duration": [0.0, 0.0], "value": [1, 2], "confidence": [null, null]}}
+	]}"#;
+	let result = littempo::parse_jams(data).unwrap();
+
+	assert_eq!(result.sections().len(), 1);
+	assert!((result.sections()[0].bpm() - 92.5).abs() < 0.0001);
+	assert!((result.sections()[0].offset() - 0.4).abs() < 0.0001);
+	assert_eq!(result.beats(), &vec![0.4, 1.05]);
+}
+
+#[test]
+fn mirex_formats() {
+	let beats = littempo::parse_mirex_beats("0.52 1\n1.03 2\r\n\n1.55\t3\n").unwrap();
+	assert_eq!(beats, vec![0.52, 1.03, 1.55]);
+	assert_eq!(littempo::parse_mirex_beats(&littempo::write_mirex_beats(&beats)).unwrap(), beats);
+
+	let tempo = littempo::parse_mirex_tempo("60.00\t120.00\t0.30\n").unwrap();
+	assert_eq!(tempo, littempo::MirexTempo::new(60., 120., 0.3));
+	assert_eq!(littempo::parse_mirex_tempo(&littempo::write_mirex_tempo(&tempo)).unwrap(), tempo);
+	assert!(littempo::parse_mirex_tempo("").is_err());
+
+	let sections = vec![
+		TempoSection::new(0., 30., 120., 0.),
+		TempoSection::new(30., 40., 90., 30.),
+	];
+	let tempo = littempo::MirexTempo::from_sections(&sections).unwrap();
+	assert_eq!(tempo.t1(), 90.);
+	assert_eq!(tempo.t2(), 120.);
+	assert!((tempo.salience() - 0.25).abs() < 0.0001);
+	assert_eq!(tempo.dominant(), 120.);
+	assert_eq!(tempo.to_sections(40.)[0].bpm(), 120.);
+
+	let tempo = littempo::MirexTempo::from_sections(&vec![TempoSection::new(0., 10., 100., 0.)]).unwrap();
+	assert_eq!(tempo, littempo::MirexTempo::new(100., 200., 1.));
+	assert_eq!(tempo.dominant(), 100.);
+	assert_eq!(littempo::parse_mirex_tempo("100\n").unwrap(), tempo);
+}
+
+#[test]
+fn global_tempo_grouping() {
+	let sections = vec![
+		TempoSection::new(0., 10., 90., 0.),
+		TempoSection::new(10., 25., 120., 10.),
+		TempoSection::new(25., 35., 90.2, 25.),
+		TempoSection::new(35., 40., 89.9, 35.),
+	];
+	assert_eq!(littempo::tempo_durations(&sections), vec![(90., 25.), (120., 15.)]);
+	assert_eq!(littempo::global_bpm(&sections), Some(90.));
+	assert_eq!(littempo::MirexTempo::from_sections(&sections).unwrap().dominant(), littempo::global_bpm(&sections).unwrap());
+	assert_eq!(littempo::global_bpm(&[]), None);
+}
@@ -0,0 +1,97 @@
+use litcontainers::*;
+use littempo::{BoundaryCandidate, BoundaryMethod, TempoSection};
+
+fn step_cyclic_tempogram() -> ContainerRM<f64, Dynamic, Dynamic> {
+	// Tempo jumps from bin 2 to bin 7 after 20 columns (10 seconds)
+	let (rows, cols) = (12, 40);
+	let mut data = vec![0.; rows * cols];
+	for c in 0..cols {
+		let bin = if c < 20 { 2 } else { 7 };
+		data[bin * cols + c] = 1.;
+	}
+	ContainerRM::from_vec(D!(rows), D!(cols), &data)
+}
+
+#[test]
+fn detect_step_boundary() {
+	let tg = step_cyclic_tempogram();
+
+	let checkerboard = littempo::detect_tempo_boundaries(&tg, 2., 3, BoundaryMethod::Checkerboard, 0.3);
+	assert_eq!(checkerboard.len(), 1);
+	assert!((checkerboard[0].time() - 10.).abs() < 0.000001);
+	assert!((checkerboard[0].strength() - 0.5).abs() < 0.000001);
+
+	let cumulative = littempo::detect_tempo_boundaries(&tg, 2., 3, BoundaryMethod::CumulativeSum, 0.);
+	assert_eq!(cumulative.len(), 1);
+	assert!((cumulative[0].time() - 10.).abs() < 0.000001);
+	assert!((cumulative[0].strength() - 2f32.sqrt()).abs() < 0.0001);
+}
+
+#[test]
+fn novelty_peaks_at_step() {
+	let tg = step_cyclic_tempogram();
+	let checkerboard = littempo::checkerboard_novelty(&littempo::self_similarity_matrix(&tg), 3);
+	let cumulative = littempo::cumulative_sum_novelty(&tg, 3);
+
+	for novelty in &[checkerboard, cumulative] {
+		assert_eq!(novelty.col_count(), 40);
+		let peak = novelty.as_iter().enumerate()
+			.max_by(|a, b| a.1.partial_cmp(b.1).unwrap()).unwrap().0;
+		assert_eq!(peak, 20);
+		// Constant regions away from the step and the edges carry no novelty
+		assert!(novelty[10].abs() < 0.000001);
+		assert!(novelty[30].abs() < 0.000001);
+	}
+}
+
+#[test]
+fn banded_checkerboard_matches_dense() {
+	let mut tg = step_cyclic_tempogram();
+	tg.as_iter_mut().enumerate().for_each(|(i, v)| *v += (i % 7) as f64 * 0.1);
+	let dense = littempo::checkerboard_novelty(&littempo::self_similarity_matrix(&tg), 4);
+	let banded = littempo::banded_checkerboard_novelty(&tg, 4);
+
+	assert_eq!(banded.col_count(), dense.col_count());
+	for (a, b) in banded.as_iter().zip(dense.as_iter()) {
+		assert!((a - b).abs() < 1e-9);
+	}
+}
+
+#[test]
+fn pick_boundary_peaks() {
+	let novelty = ContainerRM::from_vec(U1, D!(8), &vec![0., 1., 0., 0.8, 0., 0., 0.5, 0.]);
+
+	let times: Vec<f32> = littempo::pick_boundaries(&novelty, 2., 3, 0.).iter().map(|c| c.time()).collect();
+	assert_eq!(times, vec![0.5, 3.]);
+
+	let times: Vec<f32> = littempo::pick_boundaries(&novelty, 2., 1, 0.).iter().map(|c| c.time()).collect();
+	assert_eq!(times, vec![0.5, 1.5, 3.]);
+
+	let times: Vec<f32> = littempo::pick_boundaries(&novelty, 2., 3, 0.6).iter().map(|c| c.time()).collect();
+	assert_eq!(times, vec![0.5]);
+}
+
+#[test]
+fn snap_boundaries() {
+	let sections = vec![
+		TempoSection::new(0., 10., 120., 0.),
+		TempoSection::new(10., 20., 140., 10.),
+		TempoSection::new(20., 30., 100., 20.),
+	];
+	let candidates = vec![
+		BoundaryCandidate::new(11., 1.),
+		BoundaryCandidate::new(9.5, 0.5),
+		BoundaryCandidate::new(21., 1.),
+		BoundaryCandidate::new(25., 2.),
+	];
+	let bounds = |s: &Vec<TempoSection>| s.iter().map(|s| (s.start(), s.end())).collect::<Vec<_>>();
+
+	let mut snapped = sections.clone();
+	littempo::snap_section_boundaries(&mut snapped, &candidates, 2., 0.);
+	assert_eq!(bounds(&snapped), vec![(0., 11.), (11., 21.), (21., 30.)]);
+
+	// Snapping to 11 would leave the middle section 9 seconds long, 21 would do the same to the last one
+	let mut snapped = sections.clone();
+	littempo::snap_section_boundaries(&mut snapped, &candidates, 2., 9.5);
+	assert_eq!(bounds(&snapped), vec![(0., 9.5), (9.5, 20.), (20., 30.)]);
+}
@@ -0,0 +1,256 @@
+use litcontainers::*;
+use litaudio::*;
+use littempo::{TempoSection, ClickVoices, ClickVoice, ClickSource, ClickKind, ClickTrackOptionsBuilder, ClickRouting, Limiter};
+
+fn detect_onsets(samples: &Vec<f32>, sr: f64) -> Vec<f32> {
+	let silence = (0.005 * sr) as usize;
+	let mut last_sound: Option<usize> = None;
+	let mut ret = Vec::new();
+	for (i, v) in samples.iter().enumerate() {
+		if v.abs() < 0.001 { continue; }
+		if last_sound.map(|l| i - l > silence).unwrap_or(true) {
+			ret.push((i as f64 / sr) as f32);
+		}
+		last_sound = Some(i);
+	}
+	ret
+}
+
+#[test]
+fn click_track_absolute_timing() {
+	let sr = 22050.;
+	let sections = vec![
+		TempoSection::new(1.3, 5., 120., 0.2),
+		TempoSection::new(5., 8., 100., 5.35),
+	];
+	let expected = vec![1.7, 2.2, 2.7, 3.2, 3.7, 4.2, 4.7, 5.35, 5.95, 6.55, 7.15, 7.75];
+
+	let click_track = littempo::click_track(&sections, sr, 4, &ClickVoices::default()).unwrap();
+	assert_eq!(click_track.sample_count(), (8. * sr) as usize);
+
+	let samples: Vec<f32> = click_track.as_iter().cloned().collect();
+	let onsets = detect_onsets(&samples, sr);
+	assert_eq!(onsets.len(), expected.len());
+	for (onset, target) in onsets.iter().zip(expected.iter()) {
+		assert!((onset - target).abs() < 0.001, "Click at {} expected at {}", onset, target);
+	}
+}
+
+#[test]
+fn click_grid_is_continuous() {
+	let s = TempoSection::new(10., 20., 90., 3.);
+	let positions = littempo::click_positions_from_section(&s, 12);
+	let step = 60. / 90. * 4. / 12.;
+
+	assert!(positions.first().unwrap().0 >= 10. && positions.first().unwrap().0 < 10. + step);
+	assert!(positions.last().unwrap().0 < 20.);
+	for (position, _) in &positions {
+		let beats = (position - 3.) / step;
+		assert!((beats - beats.round()).abs() < 0.001);
+	}
+
+	let downbeats: Vec<_> = positions.iter().filter(|(_, k)| *k == littempo::ClickKind::Downbeat).collect();
+	for (position, _) in downbeats {
+		let bars = (position - 3.) / (step * 12.);
+		assert!((bars - bars.round()).abs() < 0.001);
+	}
+}
+
+fn peak_position(samples: &RowVec<f32, Dynamic>) -> usize {
+	samples.as_iter().enumerate()
+		.max_by(|a, b| a.1.abs().partial_cmp(&b.1.abs()).unwrap()).unwrap().0
+}
+
+#[test]
+fn sine_voice_render() {
+	let sr = 22050.;
+	let voice = ClickVoice::sine(1000., 0.1, 0.5);
+	let rendered = voice.render(sr);
+
+	assert_eq!(rendered.col_count(), (0.1f32 as f64 * sr) as usize);
+	// Decaying sine peaks within its first period
+	assert!(peak_position(&rendered) < (sr / 1000.) as usize);
+	assert!(rendered.as_iter().all(|v| v.abs() <= 0.5));
+	assert!(rendered.as_iter().any(|v| v.abs() > 0.4));
+}
+
+#[test]
+fn sample_voice_resample() {
+	let voice = ClickVoice::new(ClickSource::Sample { data: vec![0., 1., 0., 0.], sr: 1000. }, 0.5);
+
+	let same: Vec<f32> = voice.render(1000.).as_iter().cloned().collect();
+	assert_eq!(same, vec![0., 0.5, 0., 0.]);
+
+	let upsampled = voice.render(2000.);
+	assert_eq!(upsampled.col_count(), 8);
+	assert_eq!(peak_position(&upsampled), 2);
+	let upsampled: Vec<f32> = upsampled.as_iter().cloned().collect();
+	for (result, target) in upsampled.iter().zip([0., 0.25, 0.5, 0.25, 0., 0., 0., 0.].iter()) {
+		assert!((result - target).abs() < 0.000001);
+	}
+
+	assert_eq!(voice.render(500.).col_count(), 2);
+}
+
+#[test]
+fn sample_voice_from_file() {
+	let sr = 8000;
+	let mut audio = AudioDeinterleaved::new(DeinterleavedStorage::zeros(D!(2), D!(100)), sr);
+	for (i, mut ch) in audio.as_row_slice_mut_iter().enumerate() {
+		ch.as_iter_mut().skip(10).take(1).for_each(|v| *v = if i == 0 { 0.5 } else { 0.25 });
+	}
+	let path = std::env::temp_dir().join("littempo_click_voice_test.wav");
+	litaudioio::write_audio(&path, &audio).unwrap();
+
+	let voice = ClickVoice::from_file(&path, 1.).unwrap();
+	match voice.source() {
+		ClickSource::Sample { data, sr: sample_sr } => {
+			assert_eq!(data.len(), 100);
+			assert!((*sample_sr - sr as f64).abs() < 0.000001);
+		},
+		_ => panic!("Expected a sample voice"),
+	}
+
+	// Channels are mixed down to mono
+	let rendered = voice.render(sr as f64);
+	assert_eq!(peak_position(&rendered), 10);
+	assert!((rendered[10] - 0.375).abs() < 0.001);
+	std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn click_kind_accents() {
+	use littempo::click_kind;
+
+	assert_eq!(click_kind(0, 4), ClickKind::Downbeat);
+	assert_eq!(click_kind(1, 4), ClickKind::Beat);
+	assert_eq!(click_kind(4, 4), ClickKind::Downbeat);
+	assert_eq!(click_kind(-1, 4), ClickKind::Beat);
+	assert_eq!(click_kind(-4, 4), ClickKind::Downbeat);
+
+	assert_eq!(click_kind(1, 8), ClickKind::Subdivision);
+	assert_eq!(click_kind(2, 8), ClickKind::Beat);
+	assert_eq!(click_kind(8, 8), ClickKind::Downbeat);
+
+	assert_eq!(click_kind(1, 12), ClickKind::Subdivision);
+	assert_eq!(click_kind(3, 12), ClickKind::Beat);
+
+	// Whole notes only hit downbeats
+	assert_eq!(click_kind(3, 1), ClickKind::Downbeat);
+}
+
+#[test]
+fn click_kind_time_signatures() {
+	use littempo::click_kind_custom;
+
+	for i in -8..8 {
+		assert_eq!(click_kind_custom(i, 8, (4, 4)), littempo::click_kind(i, 8));
+	}
+
+	let three_four: Vec<_> = (0..6).map(|i| click_kind_custom(i, 4, (3, 4))).collect();
+	assert_eq!(three_four, vec![ClickKind::Downbeat, ClickKind::Beat, ClickKind::Beat, ClickKind::Downbeat, ClickKind::Beat, ClickKind::Beat]);
+	assert_eq!(click_kind_custom(1, 8, (3, 4)), ClickKind::Subdivision);
+	assert_eq!(click_kind_custom(6, 8, (3, 4)), ClickKind::Downbeat);
+
+	// Eighth note beats in 6/8
+	assert_eq!(click_kind_custom(6, 8, (6, 8)), ClickKind::Downbeat);
+	assert_eq!(click_kind_custom(1, 8, (6, 8)), ClickKind::Beat);
+	assert_eq!(click_kind_custom(1, 16, (6, 8)), ClickKind::Subdivision);
+}
+
+/// Stereo music with a constant level per channel.
+fn constant_music(levels: &[f32], sr: i32, len: usize) -> AudioDeinterleaved<f32, Dynamic, Dynamic> {
+	let mut ret = AudioDeinterleaved::new(DeinterleavedStorage::zeros(D!(levels.len()), D!(len)), sr);
+	for (mut ch, level) in ret.as_row_slice_mut_iter().zip(levels.iter()) {
+		ch.as_iter_mut().for_each(|v| *v = *level);
+	}
+	ret
+}
+
+fn channels(a: &AudioDeinterleaved<f32, Dynamic, Dynamic>) -> Vec<Vec<f32>> {
+	a.as_row_slice_iter().map(|ch| ch.as_iter().cloned().collect()).collect()
+}
+
+#[test]
+fn limiter_ceiling() {
+	assert_eq!(Limiter::HardClip.apply(2.), 1.);
+	assert_eq!(Limiter::HardClip.apply(-2.), -1.);
+	assert_eq!(Limiter::HardClip.apply(0.5), 0.5);
+
+	let soft = Limiter::Soft { threshold: 0.9 };
+	assert_eq!(soft.apply(0.5), 0.5);
+	assert_eq!(soft.apply(-0.9), -0.9);
+	let mut last = 0.9;
+	for v in &[0.95, 1., 1.2, 1.4] {
+		let limited = soft.apply(*v);
+		assert!(limited > last && limited < 1.);
+		assert!((soft.apply(-*v) + limited).abs() < 0.000001);
+		last = limited;
+	}
+	assert!(soft.apply(100.) <= 1.);
+
+	// Loud music plus clicks never exceeds the ceiling
+	let sr = 8000;
+	let sections = vec![TempoSection::new(0., 2., 60., 0.5)];
+	let music = constant_music(&[0.95, -0.95], sr, 2 * sr as usize);
+	for limiter in &[Limiter::HardClip, soft] {
+		let options = ClickTrackOptionsBuilder::default().limiter(*limiter).build().unwrap();
+		let output = littempo::render_tempo_click_track(&music, &sections, 4, &options);
+		assert!(output.as_iter().all(|v| v.abs() <= 1.));
+	}
+}
+
+#[test]
+fn ducking_depth() {
+	let sr = 8000;
+	let sections = vec![TempoSection::new(0., 2., 60., 0.5)];
+	let music = constant_music(&[0.5], sr, 2 * sr as usize);
+	let options = ClickTrackOptionsBuilder::default()
+		.ducking(Some(6.))
+		.ducking_release(0.05)
+		.routing(ClickRouting::Split)
+		.build().unwrap();
+	let output = channels(&littempo::render_tempo_click_track(&music, &sections, 4, &options));
+	let music = &output[0];
+	let at = |t: f64| music[(t * sr as f64) as usize];
+
+	// Untouched before the first click, attenuated by 6 dB while it sounds and recovered after the release
+	assert!((at(0.25) - 0.5).abs() < 0.000001);
+	assert!((at(0.51) - 0.5 * littempo::db_to_gain(-6.)).abs() < 0.001);
+	assert!((at(1.4) - 0.5).abs() < 0.001);
+	assert!(at(0.7) > at(0.61));
+}
+
+#[test]
+fn click_routing() {
+	let sr = 8000;
+	let len = 2 * sr as usize;
+	let sections = vec![TempoSection::new(0., 2., 60., 0.5)];
+	let music = constant_music(&[0.2, 0.4], sr, len);
+	let options = |routing: ClickRouting| ClickTrackOptionsBuilder::default()
+		.click_gain(-12.)
+		.routing(routing)
+		.build().unwrap();
+
+	let click_only = channels(&littempo::render_tempo_click_track(&music, &sections, 4, &options(ClickRouting::ClickOnly)));
+	assert_eq!(click_only.len(), 1);
+	let click = &click_only[0];
+	let target = littempo::click_track(&sections, sr as f64, 4, &ClickVoices::default()).unwrap();
+	for (result, target) in click.iter().zip(target.as_iter()) {
+		assert!((result - target * littempo::db_to_gain(-12.)).abs() < 0.000001);
+	}
+
+	let mix = channels(&littempo::render_tempo_click_track(&music, &sections, 4, &options(ClickRouting::Mix)));
+	assert_eq!(mix.len(), 2);
+	for i in 0..len {
+		assert!((mix[0][i] - 0.2 - click[i]).abs() < 0.000001);
+		assert!((mix[1][i] - 0.4 - click[i]).abs() < 0.000001);
+	}
+
+	let split = channels(&littempo::render_tempo_click_track(&music, &sections, 4, &options(ClickRouting::Split)));
+	assert_eq!(split.len(), 2);
+	for i in 0..len {
+		assert!((split[0][i] - 0.3).abs() < 0.000001);
+		assert!((split[1][i] - click[i]).abs() < 0.000001);
+	}
+}
@@ -0,0 +1,90 @@
+use litcontainers::*;
+use littempo::{TempoConstraintsBuilder, TempoSection, LockedFields, NoProgress};
+
+#[test]
+fn fit_bpm_to_range() {
+	assert_eq!(littempo::fit_bpm_to_range(88., (170., 180.)), 176.);
+	assert_eq!(littempo::fit_bpm_to_range(350., (170., 180.)), 175.);
+	assert_eq!(littempo::fit_bpm_to_range(120., (170., 180.)), 180.);
+	assert_eq!(littempo::fit_bpm_to_range(60., (100., 200.)), 120.);
+	assert_eq!(littempo::fit_bpm_to_range(130., (170., 180.)), 170.);
+}
+
+fn onset_novelty_curve(bpm: f32, offset: f32, duration: f32) -> RowVec<f64, Dynamic> {
+	let count = ((duration - offset) * bpm / 60.) as usize;
+	let onsets: Vec<f32> = (0..count).map(|i| offset + i as f32 * 60. / bpm).collect();
+	littempo::onsets_to_novelty_curve(&onsets, None, 100., Some(duration))
+}
+
+#[test]
+fn fixed_bpm_only_estimates_offset() {
+	let novelty_curve = onset_novelty_curve(128., 0.3, 40.);
+	let constraints = TempoConstraintsBuilder::default().fixed_bpm(Some(128.)).build().unwrap();
+	let settings = littempo::TempoExtractionSettingsBuilder::default().constraints(constraints).build().unwrap();
+
+	let sections = littempo::extract_tempo_from_novelty_curve(&novelty_curve, 100., &settings, &NoProgress).unwrap();
+	assert_eq!(sections.len(), 1);
+	assert_eq!(sections[0].bpm(), 128.);
+	let beat = 60. / 128.;
+	let phase = ((sections[0].offset() - 0.3) / beat).fract().abs();
+	assert!(phase < 0.05 || phase > 0.95, "{:?}", sections);
+}
+
+#[test]
+fn refine_sections_with_locked_offset() {
+	let novelty_curve = onset_novelty_curve(100., 0.2, 40.);
+	let constraints = TempoConstraintsBuilder::default()
+		.sections(vec![TempoSection::new(0., 40., 99.5, 1.)])
+		.locked(LockedFields { boundaries: true, bpm: false, offset: true })
+		.build().unwrap();
+	let settings = littempo::TempoExtractionSettingsBuilder::default().constraints(constraints).build().unwrap();
+
+	let sections = littempo::extract_tempo_from_novelty_curve(&novelty_curve, 100., &settings, &NoProgress).unwrap();
+	assert_eq!(sections.len(), 1);
+	assert_eq!(sections[0].offset(), 1.);
+	assert_eq!((sections[0].start(), sections[0].end()), (0., 40.));
+	assert!((sections[0].bpm() - 100.).abs() < 0.2, "{:?}", sections);
+}
+
+#[test]
+fn bpm_range_extraction() {
+	let novelty_curve = onset_novelty_curve(175., 0.1, 40.);
+	let constraints = TempoConstraintsBuilder::default().bpm_range(Some((170., 180.))).build().unwrap();
+	// Octave range of the preset does not contain the bpm range and is replaced
+	let cyclic_settings = littempo::CyclicTempogramSettingsBuilder::default().octave_range(Some((-2, -1))).build().unwrap();
+	let settings = littempo::TempoExtractionSettingsBuilder::default()
+		.constraints(constraints)
+		.cyclic_tempogram_settings(cyclic_settings)
+		.build().unwrap();
+
+	let bpms = settings.constraints().narrow_scan_bpms(settings.scan_bpms());
+	let narrowed = settings.constraints().narrow_cyclic_settings(settings.cyclic_tempogram_settings(), &bpms, *settings.ref_tempo());
+	assert_eq!(*narrowed.octave_range(), Some((1, 2)));
+
+	let analysis = littempo::analyze_tempogram(&novelty_curve, 100., &settings, &NoProgress).unwrap();
+	assert!(analysis.cyclic_tempogram().as_iter().any(|v| *v > 0.));
+
+	let sections = littempo::extract_tempo_from_novelty_curve(&novelty_curve, 100., &settings, &NoProgress).unwrap();
+	assert!(!sections.is_empty());
+	for s in &sections {
+		assert!(s.bpm() >= 170. && s.bpm() <= 180., "{:?}", sections);
+		assert!((s.bpm() - 175.).abs() <= 0.5, "{:?}", sections);
+	}
+}
+
+#[test]
+fn fixed_bpm_skips_tempogram_analysis() {
+	let sections = vec![littempo::SyntheticSectionBuilder::default().start(0.).end(20.).bpm(128.).build().unwrap()];
+	let signal = littempo::synthesize_tempo_map(&sections, &littempo::SynthesisSettings::default());
+	let constraints = TempoConstraintsBuilder::default().fixed_bpm(Some(128.)).build().unwrap();
+	let settings = littempo::TempoExtractionSettingsBuilder::default().constraints(constraints).build().unwrap();
+
+	let extraction = littempo::extract_tempo_detailed(signal.audio(), &settings, &NoProgress).unwrap();
+	assert!(extraction.analysis().is_none());
+	assert_eq!(extraction.sections().len(), 1);
+	assert_eq!(extraction.sections()[0].bpm(), 128.);
+
+	let svg = littempo::render_svg_report(signal.audio(), extraction.novelty_curve(), *extraction.nc_sr(), None, extraction.sections());
+	assert!(svg.contains("Novelty Curve"));
+	assert!(!svg.contains("Tempogram"));
+}
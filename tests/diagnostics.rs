@@ -0,0 +1,19 @@
+use litcontainers::*;
+use littempo::AudioIssue;
+
+#[test]
+fn inspect_audio_issues() {
+	let sr = 1000.;
+	let quiet = RowVec::regspace_rows(U1, D!(2000), 0.).sin() * 0.0001;
+	assert_eq!(littempo::inspect_audio(&quiet, sr, 1.), vec![AudioIssue::NearSilent { peak: quiet.as_iter().fold(0., |a: f64, v| a.max(v.abs())) }]);
+
+	let mut clipped = RowVec::regspace_rows(U1, D!(500), 0.).sin() * 0.5;
+	clipped.as_iter_mut().step_by(10).for_each(|v| *v = 1.);
+	let issues = littempo::inspect_audio(&clipped, sr, 1.);
+	assert_eq!(issues.len(), 2);
+	assert_eq!(issues[0], AudioIssue::Short { duration: 0.5 });
+	match issues[1] {
+		AudioIssue::Clipped { fraction } => assert!((fraction - 0.1).abs() < 0.001),
+		_ => panic!("Expected clipping"),
+	}
+}
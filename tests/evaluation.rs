@@ -0,0 +1,65 @@
+use littempo::{TempoSection, EvaluationSettings};
+
+fn grid(start: f32, step: f32, count: usize) -> Vec<f32> {
+	(0..count).map(|i| start + i as f32 * step).collect()
+}
+
+#[test]
+fn perfect_beats() {
+	let beats = grid(0., 0.5, 100);
+	let scores = littempo::evaluate_beats(&beats, &beats, &EvaluationSettings::default());
+
+	for v in &[scores.f_measure(), scores.cemgil(), scores.p_score(), scores.cml_c(), scores.cml_t(), scores.aml_c(), scores.aml_t()] {
+		assert!((**v - 1.).abs() < 0.0001, "{:?}", scores);
+	}
+	assert!((scores.information_gain() - 41f32.log2()).abs() < 0.0001, "{:?}", scores);
+}
+
+#[test]
+fn information_gain_histogram() {
+	let reference = grid(0., 0.5, 100);
+	// Errors of exactly half a beat land in the merged +-0.5 bin, giving the full gain
+	let off_beat = grid(0.25, 0.5, 99);
+	assert!((littempo::information_gain(&off_beat, &reference, 41) - 41f32.log2()).abs() < 0.0001);
+
+	// Errors of a quarter bin width stay in the centre bin
+	let width = 0.5 / 40.;
+	let close = grid(width / 4., 0.5, 99);
+	assert!((littempo::information_gain(&close, &reference, 41) - 41f32.log2()).abs() < 0.0001);
+
+	// Uniformly spread errors carry almost no information
+	let spread: Vec<f32> = (0..100).map(|i| i as f32 * 0.5 + (i % 40) as f32 * width).collect();
+	assert!(littempo::information_gain(&spread, &reference, 41) < 0.5);
+}
+
+#[test]
+fn metrical_level_errors() {
+	let reference = grid(0., 0.5, 100);
+	let settings = EvaluationSettings::default();
+
+	// Off-beat
+	let scores = littempo::evaluate_beats(&grid(0.25, 0.5, 99), &reference, &settings);
+	assert!(*scores.f_measure() < 0.0001);
+	assert!(*scores.cml_t() < 0.0001);
+	assert!((scores.aml_t() - 1.).abs() < 0.0001);
+
+	// Double tempo
+	let scores = littempo::evaluate_beats(&grid(0., 0.25, 199), &reference, &settings);
+	assert!(*scores.cml_c() < 0.0001);
+	assert!((scores.aml_c() - 1.).abs() < 0.0001);
+}
+
+#[test]
+fn tempo_accuracy() {
+	let reference = vec![TempoSection::new(0., 30., 120., 0.)];
+	let settings = EvaluationSettings::default();
+
+	let scores = littempo::evaluate_tempo(&vec![TempoSection::new(0., 30., 123., 0.)], &reference, &settings);
+	assert_eq!((*scores.accuracy1(), *scores.accuracy2()), (1., 1.));
+
+	let scores = littempo::evaluate_tempo(&vec![
+		TempoSection::new(0., 15., 240., 0.),
+		TempoSection::new(15., 30., 100., 0.),
+	], &reference, &settings);
+	assert_eq!((*scores.accuracy1(), *scores.accuracy2()), (0., 0.5));
+}
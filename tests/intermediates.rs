@@ -0,0 +1,59 @@
+use litcontainers::*;
+use littempo::{SyntheticSectionBuilder, SynthesisSettings, DumpFormat, NoProgress};
+
+#[test]
+fn dump_npy_and_csv() {
+	let sections = vec![SyntheticSectionBuilder::default().start(0.).end(20.).bpm(120.).build().unwrap()];
+	let signal = littempo::synthesize_tempo_map(&sections, &SynthesisSettings::default());
+	let settings = littempo::TempoExtractionSettingsBuilder::default().build().unwrap();
+	let extraction = littempo::extract_tempo_detailed(signal.audio(), &settings, &NoProgress).unwrap();
+	let dir = std::env::temp_dir().join("littempo_intermediates_test");
+
+	littempo::save_intermediates(&dir, DumpFormat::Npy, &extraction).unwrap();
+	let data = std::fs::read(dir.join("novelty_curve.npy")).unwrap();
+	let header_len = u16::from_le_bytes([data[8], data[9]]) as usize;
+	assert_eq!(&data[..6], b"\x93NUMPY");
+	assert_eq!((10 + header_len) % 64, 0);
+	assert_eq!(data.len() - 10 - header_len, extraction.novelty_curve().as_iter().count() * 8);
+	let metadata: serde_json::Value = serde_json::from_slice(&std::fs::read(dir.join("tempogram.json")).unwrap()).unwrap();
+	assert_eq!(metadata["dtype"], "complex128");
+	assert_eq!(metadata["axis_name"], "bpm");
+
+	littempo::save_intermediates(&dir, DumpFormat::Csv, &extraction).unwrap();
+	let csv = std::fs::read_to_string(dir.join("cyclic_tempogram.csv")).unwrap();
+	assert!(csv.starts_with("# name=cyclic_tempogram dtype=float64"));
+	assert_eq!(csv.lines().count(), 2 + extraction.analysis().as_ref().unwrap().cyclic_tempogram().as_row_slice_iter().next().unwrap().as_iter().count());
+}
+
+#[test]
+fn dump_band_axis_and_npz() {
+	let sections = vec![SyntheticSectionBuilder::default().start(0.).end(20.).bpm(120.).build().unwrap()];
+	let signal = littempo::synthesize_tempo_map(&sections, &SynthesisSettings::default());
+	let settings = littempo::TempoExtractionSettingsBuilder::default()
+		.analysis_band_bins(Some(ContainerRM::from_vec(D!(2), U2, &vec![0., 800., 800., 4000.])))
+		.build().unwrap();
+	let extraction = littempo::extract_tempo_detailed(signal.audio(), &settings, &NoProgress).unwrap();
+	let dir = std::env::temp_dir().join("littempo_intermediates_band_test");
+
+	littempo::save_intermediates(&dir, DumpFormat::Npy, &extraction).unwrap();
+	let metadata: serde_json::Value = serde_json::from_slice(&std::fs::read(dir.join("band_odf.json")).unwrap()).unwrap();
+	assert_eq!(metadata["axis_name"], "band_hz");
+	assert_eq!(metadata["axis"], serde_json::json!([[0., 800.], [800., 4000.]]));
+
+	littempo::save_intermediates(&dir, DumpFormat::Csv, &extraction).unwrap();
+	let csv = std::fs::read_to_string(dir.join("band_odf.csv")).unwrap();
+	assert_eq!(csv.lines().nth(1), Some("time,0-800,800-4000"));
+
+	littempo::save_intermediates(&dir, DumpFormat::Npz, &extraction).unwrap();
+	let mut archive = zip::ZipArchive::new(std::fs::File::open(dir.join("band_odf.npz")).unwrap()).unwrap();
+	let mut names: Vec<String> = (0..archive.len()).map(|i| archive.by_index(i).unwrap().name().to_string()).collect();
+	names.sort();
+	assert_eq!(names, vec!["axis.npy", "axis_name.npy", "data.npy", "name.npy", "sr.npy"]);
+
+	let mut name = Vec::new();
+	std::io::Read::read_to_end(&mut archive.by_name("name.npy").unwrap(), &mut name).unwrap();
+	let header = String::from_utf8_lossy(&name[10..64]).to_string();
+	assert!(header.contains("'descr': '<U8'"), "{}", header);
+	let text: String = name[64..].chunks(4).map(|c| std::char::from_u32(u32::from_le_bytes([c[0], c[1], c[2], c[3]])).unwrap()).collect();
+	assert_eq!(text, "band_odf");
+}
@@ -0,0 +1,100 @@
+use littempo::{TempoSection, MarkerFormat};
+
+fn sections() -> Vec<TempoSection> {
+	vec![
+		TempoSection::new(0., 10., 120., 0.25),
+		TempoSection::new(10., 20., 100., 10.3),
+	]
+}
+
+fn beats() -> Vec<f32> { vec![0.5, 1., 1.5] }
+
+#[test]
+fn audacity_labels() {
+	assert_eq!(
+		littempo::sections_to_markers(&sections(), MarkerFormat::Audacity),
+		"0.000000\t10.000000\t120.00 BPM (offset 0.250)\n\
+		10.000000\t20.000000\t100.00 BPM (offset 10.300)\n"
+	);
+	assert_eq!(
+		littempo::beats_to_markers(&beats(), MarkerFormat::Audacity),
+		"0.500000\t0.500000\t1\n1.000000\t1.000000\t2\n1.500000\t1.500000\t3\n"
+	);
+}
+
+#[test]
+fn reaper_project() {
+	assert_eq!(
+		littempo::sections_to_markers(&sections(), MarkerFormat::Reaper),
+		"<REAPER_PROJECT 0.1 \"5.0\" 0\n\
+		\x20 TEMPO 120.000000 4 4\n\
+		\x20 <TEMPOENVEX\n\
+		\x20   PT 0.000000 120.000000 1\n\
+		\x20   PT 10.000000 100.000000 1\n\
+		\x20 >\n\
+		\x20 MARKER 1 0.000000 \"120.00 BPM (offset 0.250)\" 0\n\
+		\x20 MARKER 2 10.000000 \"100.00 BPM (offset 10.300)\" 0\n\
+		>\n"
+	);
+	assert_eq!(
+		littempo::beats_to_markers(&beats(), MarkerFormat::Reaper),
+		"<REAPER_PROJECT 0.1 \"5.0\" 0\n\
+		\x20 TEMPO 120.000000 4 4\n\
+		\x20 MARKER 1 0.500000 \"1\" 0\n\
+		\x20 MARKER 2 1.000000 \"2\" 0\n\
+		\x20 MARKER 3 1.500000 \"3\" 0\n\
+		>\n"
+	);
+}
+
+#[test]
+fn sonic_visualiser_layers() {
+	assert_eq!(
+		littempo::sections_to_markers(&sections(), MarkerFormat::SonicVisualiser),
+		"0.000000,120.000,10.000000,\"120.00 BPM (offset 0.250)\"\n\
+		10.000000,100.000,10.000000,\"100.00 BPM (offset 10.300)\"\n"
+	);
+	assert_eq!(
+		littempo::beats_to_markers(&beats(), MarkerFormat::SonicVisualiser),
+		"0.500000,\"1\"\n1.000000,\"2\"\n1.500000,\"3\"\n"
+	);
+}
+
+#[test]
+fn csv_and_tsv() {
+	assert_eq!(
+		littempo::sections_to_markers(&sections(), MarkerFormat::Csv),
+		"start,end,bpm,offset\n\
+		0.000000,10.000000,120.000,0.250000\n\
+		10.000000,20.000000,100.000,10.300000\n"
+	);
+	assert_eq!(
+		littempo::sections_to_markers(&sections(), MarkerFormat::Tsv),
+		"start\tend\tbpm\toffset\n\
+		0.000000\t10.000000\t120.000\t0.250000\n\
+		10.000000\t20.000000\t100.000\t10.300000\n"
+	);
+	let beats_csv = "time\n0.500000\n1.000000\n1.500000\n";
+	assert_eq!(littempo::beats_to_markers(&beats(), MarkerFormat::Csv), beats_csv);
+	assert_eq!(littempo::beats_to_markers(&beats(), MarkerFormat::Tsv), beats_csv);
+}
+
+#[test]
+fn json_and_osu() {
+	assert_eq!(
+		littempo::beats_to_markers(&beats(), MarkerFormat::Json),
+		"{\n  \"beats\": [\n    0.5,\n    1.0,\n    1.5\n  ]\n}\n"
+	);
+	let json: serde_json::Value = serde_json::from_str(&littempo::sections_to_markers(&sections(), MarkerFormat::Json)).unwrap();
+	assert_eq!(json["sections"].as_array().unwrap().len(), 2);
+	assert_eq!(json["sections"][1]["bpm"].as_f64(), Some(100.));
+
+	assert_eq!(
+		littempo::sections_to_markers(&sections(), MarkerFormat::Osu),
+		"[TimingPoints]\n250,500,4,1,0,100,1,0\n10300,600,4,1,0,100,1,0\n"
+	);
+	assert_eq!(
+		littempo::beats_to_markers(&beats(), MarkerFormat::Osu),
+		"[HitObjects]\n256,192,500,1,0,0:0:0:0:\n256,192,1000,1,0,0:0:0:0:\n256,192,1500,1,0,0:0:0:0:\n"
+	);
+}
@@ -0,0 +1,92 @@
+use littempo::{TempoSection, MidiExportSettingsBuilder};
+
+#[test]
+fn tempo_map_midi() {
+	let sections = vec![
+		TempoSection::new(0., 10., 120., 0.),
+		TempoSection::new(10., 20., 100., 10.),
+	];
+	let settings = MidiExportSettingsBuilder::default().click_fraction(None).build().unwrap();
+	let midi = littempo::tempo_sections_to_midi(&sections, &settings);
+
+	assert_eq!(&midi[0..4], b"MThd");
+	assert_eq!(&midi[10..12], &[0, 1]); // Single track
+	assert_eq!(&midi[14..18], b"MTrk");
+
+	let track = &midi[22..];
+	// 120 bpm at tick 0
+	assert_eq!(&track[0..7], &[0x00, 0xFF, 0x51, 0x03, 0x07, 0xA1, 0x20]);
+	// 100 bpm after 20 quarters (9600 ticks)
+	let pos = track.windows(3).rposition(|w| w == [0xFF, 0x51, 0x03]).unwrap();
+	assert_eq!(&track[pos - 2..pos], &[0xCB, 0x00]);
+	assert_eq!(&track[pos + 3..pos + 6], &[0x09, 0x27, 0xC0]);
+	assert_eq!(&track[track.len() - 3..], &[0xFF, 0x2F, 0x00]);
+	// Time signature is written once
+	assert_eq!(track.windows(3).filter(|w| *w == [0xFF, 0x58, 0x04]).count(), 1);
+}
+
+#[test]
+fn click_track_midi() {
+	let sections = vec![TempoSection::new(0., 4., 120., 0.5)];
+	let midi = littempo::tempo_sections_to_midi(&sections, &Default::default());
+
+	assert_eq!(&midi[10..12], &[0, 2]);
+	let note_ons = midi.windows(2).filter(|w| w[0] == 0x99).count();
+	assert_eq!(note_ons, 8);
+}
+
+#[test]
+fn click_track_midi_three_four() {
+	// 12 quarter notes at 120 bpm
+	let sections = vec![TempoSection::new(0., 6., 120., 0.)];
+	let downbeats = |midi: &Vec<u8>| midi.windows(3).filter(|w| w[0] == 0x99 && w[1] == 76 && w[2] > 0).count();
+	let beats = |midi: &Vec<u8>| midi.windows(3).filter(|w| w[0] == 0x99 && w[1] == 77 && w[2] > 0).count();
+
+	let midi = littempo::tempo_sections_to_midi(&sections, &Default::default());
+	assert_eq!((downbeats(&midi), beats(&midi)), (3, 9));
+
+	let settings = MidiExportSettingsBuilder::default().time_signature((3, 4)).build().unwrap();
+	let midi = littempo::tempo_sections_to_midi(&sections, &settings);
+	assert!(midi.windows(7).any(|w| w == [0xFF, 0x58, 0x04, 3, 2, 24, 8]));
+	assert_eq!((downbeats(&midi), beats(&midi)), (4, 8));
+
+	let kinds: Vec<_> = littempo::click_positions_custom(&sections, 4, (3, 4)).into_iter().map(|(_, k)| k).collect();
+	for (i, kind) in kinds.iter().enumerate() {
+		let target = if i % 3 == 0 { littempo::ClickKind::Downbeat } else { littempo::ClickKind::Beat };
+		assert_eq!(*kind, target);
+	}
+}
+
+#[test]
+fn tempo_map_midi_lead_in() {
+	// First downbeat at 0.3s is preceded by a single beat at 200 bpm
+	let sections = vec![TempoSection::new(0., 10., 120., 0.3)];
+	let settings = MidiExportSettingsBuilder::default().time_signature((3, 4)).click_fraction(None).build().unwrap();
+	let midi = littempo::tempo_sections_to_midi(&sections, &settings);
+	let track = &midi[22..];
+
+	assert_eq!(&track[0..7], &[0x00, 0xFF, 0x51, 0x03, 0x04, 0x93, 0xE0]);
+	assert_eq!(&track[7..15], &[0x00, 0xFF, 0x58, 0x04, 1, 2, 24, 8]);
+	// 120 bpm and the 3/4 bar start after one quarter (480 ticks)
+	assert_eq!(&track[15..23], &[0x83, 0x60, 0xFF, 0x51, 0x03, 0x07, 0xA1, 0x20]);
+	assert_eq!(&track[23..31], &[0x00, 0xFF, 0x58, 0x04, 3, 2, 24, 8]);
+
+	// Downbeat clicks land on bar lines
+	let midi = littempo::tempo_sections_to_midi(&sections, &MidiExportSettingsBuilder::default().time_signature((3, 4)).build().unwrap());
+	let mut tick = 0;
+	let mut downbeat_ticks = Vec::new();
+	let mut track = &midi[midi.windows(4).rposition(|w| w == b"MTrk").unwrap() + 8..];
+	while track.len() > 3 {
+		let mut delta = 0u32;
+		let mut i = 0;
+		loop {
+			delta = (delta << 7) | (track[i] & 0x7F) as u32;
+			i += 1;
+			if track[i - 1] & 0x80 == 0 { break; }
+		}
+		tick += delta;
+		if track[i] == 0x99 && track[i + 1] == 76 { downbeat_ticks.push(tick); }
+		track = &track[i + 3..];
+	}
+	assert_eq!(downbeat_ticks[..3], [480, 1920, 3360]);
+}
@@ -0,0 +1,45 @@
+use litcontainers::*;
+use littempo::{NoProgress, TempoSection, EvaluationSettings};
+
+#[test]
+fn onsets_to_novelty_curve_peaks() {
+	let onsets = vec![0.5, 1.];
+	let novelty_curve = littempo::onsets_to_novelty_curve(&onsets, Some(&[2., 1.]), 100., None);
+
+	assert_eq!(novelty_curve.col_count(), 200);
+	assert!((novelty_curve[50] - 2.).abs() < 1e-9);
+	assert!((novelty_curve[100] - 1.).abs() < 1e-9);
+	assert_eq!(novelty_curve[75], 0.);
+}
+
+#[test]
+fn tempo_from_onsets() {
+	let onsets: Vec<f32> = (0..120).map(|i| 0.25 + i as f32 * 0.5).collect();
+	let novelty_curve = littempo::onsets_to_novelty_curve(&onsets, None, 100., None);
+	let settings = littempo::TempoExtractionSettingsBuilder::default().build().unwrap();
+
+	let sections = littempo::extract_tempo_from_novelty_curve(&novelty_curve, 100., &settings, &NoProgress).unwrap();
+	let reference = vec![TempoSection::new(0., 60., 120., 0.25)];
+	let scores = littempo::evaluate_tempo(&sections, &reference, &EvaluationSettings::default());
+	assert!((scores.accuracy2() - 1.).abs() < 0.01, "{:?}", sections);
+	for s in &sections {
+		assert!((s.bpm() - 120.).abs() <= 0.1, "{:?}", sections);
+		let phase = (s.offset() - 0.25).rem_euclid(0.5);
+		assert!(phase.min(0.5 - phase) < 0.02, "{:?}", sections);
+	}
+}
+
+#[test]
+fn timed_novelty_curve_padding() {
+	let times: Vec<f64> = (0..5).map(|i| 2. + i as f64 * 0.01).collect();
+	let (novelty_curve, sr) = littempo::timed_novelty_curve(&times, &[1., 2., 3., 4., 5.]).unwrap();
+	assert!((sr - 100.).abs() < 1e-6);
+	assert_eq!(novelty_curve.col_count(), 205);
+	assert_eq!(novelty_curve[199], 0.);
+	assert_eq!(novelty_curve[200], 1.);
+
+	assert!(littempo::timed_novelty_curve(&[0.], &[1.]).is_err());
+	assert!(littempo::timed_novelty_curve(&[1., 1.], &[1., 2.]).is_err());
+	assert!(littempo::timed_novelty_curve(&[0., 0.2, 0.1], &[1., 2., 3.]).is_err());
+	assert!(littempo::timed_novelty_curve(&[-1., 0.], &[1., 2.]).is_err());
+}
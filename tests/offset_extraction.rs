@@ -0,0 +1,35 @@
+use litcontainers::*;
+use littempo::{SyntheticSectionBuilder, SynthesisSettings, TempoSection, NoProgress, CancellationToken, Cancelled};
+
+#[test]
+fn offset_landscape() {
+	let sections = vec![SyntheticSectionBuilder::default().start(0.).end(20.).bpm(120.).offset(Some(0.25)).build().unwrap()];
+	let signal = littempo::synthesize_tempo_map(&sections, &SynthesisSettings::default());
+	let settings = littempo::TempoExtractionSettingsBuilder::default().build().unwrap();
+	let (novelty_curve, nc_sr) = littempo::extract_novelty_curve(signal.audio(), &settings, &NoProgress).unwrap();
+
+	let mut section = TempoSection::new(0., 20., 120., 0.);
+	let landscape = littempo::extract_offset(&novelty_curve, nc_sr, &mut section, &vec![1., 2.], 2., 0.1);
+
+	assert_eq!(landscape.bpms().col_count(), 20);
+	assert_eq!(landscape.scores().row_count(), 20);
+	assert_eq!(landscape.scores().col_count(), landscape.shifts().col_count());
+
+	// The chosen bpm and offset have the highest score
+	let best = landscape.scores().as_iter().cloned().fold(0., f64::max);
+	let row = (0..20).find(|r| (landscape.bpms()[*r] as f32 - section.bpm()).abs() < 0.001).unwrap();
+	assert!((landscape.scores().slice_rows(row).maximum() - best).abs() < 1e-9);
+}
+
+#[test]
+fn offset_extraction_cancelled() {
+	let novelty_curve: RowVec<f64, Dynamic> = ContainerRM::zeros(U1, D!(1000));
+	let token = CancellationToken::new();
+	token.cancel();
+
+	let mut section = TempoSection::new(0., 10., 120., 1.);
+	let result = littempo::extract_offset_custom(&novelty_curve, 100., &mut section, &vec![1.], 2., 0.1, &token);
+	assert_eq!(result.err(), Some(Cancelled));
+	assert_eq!(section.bpm(), 120.);
+	assert_eq!(section.offset(), 1.);
+}
@@ -0,0 +1,115 @@
+use litcontainers::*;
+
+fn step_tempogram() -> ContainerRM<f64, U2, Dynamic> {
+	// Two tempo bins with an abrupt change halfway
+	let mut data = vec![0.; 40];
+	for i in 0..10 { data[i] = 1.; }
+	for i in 10..20 { data[20 + i] = 1.; }
+	ContainerRM::from_vec(U2, D!(20), &data)
+}
+
+#[test]
+fn median_filter_preserves_edges() {
+	let tg = step_tempogram();
+	let filtered = littempo::median_filter_cols(&tg, 5);
+
+	for (target, result) in tg.iter().zip(filtered.iter()) {
+		assert!((target - result).abs() < 0.000001);
+	}
+}
+
+#[test]
+fn bilateral_filter_preserves_edges() {
+	let tg = step_tempogram();
+	let gaussian = littempo::apply_window_cols(&tg, &littempo::gaussian_window(D!(7)));
+	let bilateral = littempo::bilateral_filter_cols(&tg, &littempo::gaussian_window(D!(7)), 0.1);
+
+	let error = |s: &ContainerRM<f64, U2, Dynamic>| tg.iter().zip(s.iter()).map(|(a, b)| (a - b).abs()).sum::<f64>();
+	assert!(error(&bilateral) < 0.000001);
+	assert!(error(&gaussian) > 0.1);
+}
+
+/// Tempogram with unit intensity at the given bins in every column.
+fn bins_tempogram(rows: usize, bins: &[usize]) -> ContainerRM<f64, Dynamic, Dynamic> {
+	let cols = 4;
+	let mut data = vec![0.; rows * cols];
+	for bin in bins {
+		for c in 0..cols { data[bin * cols + c] = 1.; }
+	}
+	ContainerRM::from_vec(D!(rows), D!(cols), &data)
+}
+
+fn cyclic_axis() -> RowVec<f64, Dynamic> {
+	let data: Vec<f64> = (0..12).map(|k| 2f64.powf(k as f64 / 12.)).collect();
+	ContainerRM::from_vec(U1, D!(12), &data)
+}
+
+fn linear_axis() -> RowVec<f64, Dynamic> {
+	RowVec::regspace_rows(U1, D!(181), 60.)
+}
+
+fn row(s: &ContainerRM<f64, Dynamic, Dynamic>, i: usize) -> Vec<f64> {
+	s.slice_rows(i).as_iter().cloned().collect()
+}
+
+#[test]
+fn reinforce_cyclic_ratios() {
+	// Bin 7 lies a fifth (3/2) above bin 0, bin 7 * 3/2 wraps around to bin 2
+	let tg = bins_tempogram(12, &[0, 7]);
+	let axis = cyclic_axis();
+	let result = littempo::reinforce_metrical_ratios(&tg, &axis, &[(3. / 2., 0.5)], true);
+
+	assert!(row(&result, 0).iter().all(|v| (v - 1.5).abs() < 0.000001));
+	assert!(row(&result, 7).iter().all(|v| (v - 1.).abs() < 0.000001));
+	assert!(row(&result, 2).iter().all(|v| v.abs() < 0.000001));
+
+	let triplets = littempo::include_triplets(&tg, &axis, 0.5);
+	for (target, result) in result.iter().zip(triplets.iter()) {
+		assert!((target - result).abs() < 0.000001);
+	}
+}
+
+#[test]
+fn reinforce_linear_ratios() {
+	// 80 and 120 bpm are related by 3/2, 120 * 3/2 = 180 carries no intensity, 200 * 3/2 is off the axis
+	let tg = bins_tempogram(181, &[20, 60, 140]);
+	let axis = linear_axis();
+	let result = littempo::reinforce_metrical_ratios(&tg, &axis, &[(3. / 2., 0.5)], false);
+
+	assert!(row(&result, 20).iter().all(|v| (v - 1.5).abs() < 0.000001));
+	assert!(row(&result, 60).iter().all(|v| (v - 1.).abs() < 0.000001));
+	assert!(row(&result, 140).iter().all(|v| (v - 1.).abs() < 0.000001));
+	assert!(row(&result, 120).iter().all(|v| v.abs() < 0.000001));
+}
+
+#[test]
+fn dominant_cyclic_ratio() {
+	let axis = cyclic_axis();
+	let ratios = [(3. / 2., 1.), (5. / 4., 1.)];
+
+	let fifth = bins_tempogram(12, &[0, 7]);
+	assert_eq!(littempo::estimate_dominant_ratio(&fifth, &axis, &ratios, true), Some((3. / 2., 1.)));
+	let third = bins_tempogram(12, &[0, 4]);
+	assert_eq!(littempo::estimate_dominant_ratio(&third, &axis, &ratios, true), Some((5. / 4., 1.)));
+
+	// Weights are kept for reinforcing but do not bias the choice
+	let weighted = [(3. / 2., 0.2), (5. / 4., 3.)];
+	assert_eq!(littempo::estimate_dominant_ratio(&fifth, &axis, &weighted, true), Some((3. / 2., 0.2)));
+	assert_eq!(littempo::estimate_dominant_ratio(&third, &axis, &weighted, true), Some((5. / 4., 3.)));
+
+	let silent = bins_tempogram(12, &[]);
+	assert_eq!(littempo::estimate_dominant_ratio(&silent, &axis, &ratios, true), None);
+}
+
+#[test]
+fn dominant_linear_ratio() {
+	let axis = linear_axis();
+	let ratios = [(3. / 2., 1.), (5. / 4., 1.)];
+
+	// 80 -> 120 bpm
+	let fifth = bins_tempogram(181, &[20, 60]);
+	assert_eq!(littempo::estimate_dominant_ratio(&fifth, &axis, &ratios, false), Some((3. / 2., 1.)));
+	// 80 -> 100 bpm
+	let third = bins_tempogram(181, &[20, 40]);
+	assert_eq!(littempo::estimate_dominant_ratio(&third, &axis, &ratios, false), Some((5. / 4., 1.)));
+}
@@ -0,0 +1,81 @@
+use litcontainers::*;
+use littempo::{TempoExtractionSettingsBuilder, SmoothingFilter, CyclicTempogramSettingsBuilder, ClickTrackOptionsBuilder};
+
+#[test]
+fn preset_round_trip() {
+	let settings = TempoExtractionSettingsBuilder::default()
+		.preferred_bpm(100.)
+		.metrical_ratios(vec![(1.5, 3.), (1.25, 1.)])
+		.smoothing_filter(SmoothingFilter::Bilateral { range_sigma: 0.5 })
+		.boundary_snap_distance(Some(2.))
+		.build().unwrap();
+
+	let mut builder = TempoExtractionSettingsBuilder::default();
+	littempo::apply_preset(&mut builder, &littempo::settings_to_preset(&settings)).unwrap();
+	let result = builder.build().unwrap();
+
+	assert_eq!(littempo::settings_to_preset(&settings), littempo::settings_to_preset(&result));
+	assert_eq!(*result.preferred_bpm(), 100.);
+	assert_eq!(result.metrical_ratios(), &vec![(1.5, 3.), (1.25, 1.)]);
+	assert_eq!(result.scan_bpms().col_count(), settings.scan_bpms().col_count());
+}
+
+#[test]
+fn apply_setting_errors() {
+	let mut builder = TempoExtractionSettingsBuilder::default();
+	assert!(littempo::apply_setting(&mut builder, "tempo_window", "abc").is_err());
+	assert!(littempo::apply_setting(&mut builder, "unknown", "1").is_err());
+	assert!(littempo::apply_setting(&mut builder, "boundary_snap_distance", "none").is_ok());
+}
+
+#[test]
+fn nested_settings_preset() {
+	let settings = TempoExtractionSettingsBuilder::default()
+		.analysis_band_bins(Some(ContainerRM::from_vec(D!(2), U2, &vec![0., 800., 800., 4000.])))
+		.cyclic_tempogram_settings(CyclicTempogramSettingsBuilder::default()
+			.interpolation(littempo::Interpolation::Cubic)
+			.octave_range(Some((-1, 3)))
+			.octave_weights(vec![0.5, 1., 1., 0.5])
+			.pooling(littempo::OctavePooling::Max)
+			.build().unwrap())
+		.click_track_options(ClickTrackOptionsBuilder::default()
+			.click_gain(-6.)
+			.ducking(Some(3.))
+			.limiter(littempo::Limiter::HardClip)
+			.routing(littempo::ClickRouting::Split)
+			.build().unwrap())
+		.build().unwrap();
+
+	let preset = littempo::settings_to_preset(&settings);
+	assert!(preset.contains("analysis_band_bins = [[0.0, 800.0], [800.0, 4000.0]]"));
+	let mut builder = TempoExtractionSettingsBuilder::default();
+	littempo::apply_preset(&mut builder, &preset).unwrap();
+	let result = builder.build().unwrap();
+
+	assert_eq!(preset, littempo::settings_to_preset(&result));
+	let bands: Vec<f64> = result.analysis_band_bins().as_ref().unwrap().as_iter().cloned().collect();
+	assert_eq!(bands, vec![0., 800., 800., 4000.]);
+	assert_eq!(*result.cyclic_tempogram_settings().octave_range(), Some((-1, 3)));
+	assert_eq!(result.cyclic_tempogram_settings().octave_weights(), &vec![0.5, 1., 1., 0.5]);
+	assert_eq!(*result.click_track_options().routing(), littempo::ClickRouting::Split);
+	assert_eq!(*result.click_track_options().ducking(), Some(3.));
+}
+
+#[test]
+fn nested_settings_flags() {
+	let mut builder = TempoExtractionSettingsBuilder::default();
+	littempo::apply_setting(&mut builder, "cyclic_tempogram_settings", "interpolation=linear; octave_range=1:2").unwrap();
+	littempo::apply_setting(&mut builder, "click_track_options", "limiter=soft:0.8;routing=click-only").unwrap();
+	littempo::apply_setting(&mut builder, "analysis_band_bins", "0:500,500:2000").unwrap();
+	let result = builder.build().unwrap();
+
+	assert_eq!(*result.cyclic_tempogram_settings().interpolation(), littempo::Interpolation::Linear);
+	assert_eq!(*result.cyclic_tempogram_settings().pooling(), littempo::OctavePooling::Mean);
+	assert_eq!(*result.click_track_options().limiter(), littempo::Limiter::Soft { threshold: 0.8 });
+	assert_eq!(result.analysis_band_bins().as_ref().unwrap().row_count(), 2);
+
+	assert!(littempo::apply_setting(&mut builder, "cyclic_tempogram_settings", "octave_range=2:1").is_err());
+	assert!(littempo::apply_setting(&mut builder, "cyclic_tempogram_settings", "unknown=1").is_err());
+	assert!(littempo::apply_setting(&mut builder, "click_track_options", "voices=x").is_err());
+	assert!(littempo::apply_setting(&mut builder, "analysis_band_bins", "500:0").is_err());
+}
@@ -0,0 +1,33 @@
+use littempo::{SyntheticSectionBuilder, SynthesisSettings, ProgressObserver, Stage, Cancelled};
+use std::sync::Mutex;
+
+struct Recorder {
+	stages: Mutex<Vec<Stage>>,
+	cancel_at: Option<Stage>,
+}
+
+impl ProgressObserver for Recorder {
+	fn stage_enter(&self, stage: Stage) { self.stages.lock().unwrap().push(stage) }
+
+	fn is_cancelled(&self) -> bool {
+		self.cancel_at.map(|s| self.stages.lock().unwrap().contains(&s)).unwrap_or(false)
+	}
+}
+
+#[test]
+fn observer_stages_and_cancellation() {
+	let sections = vec![SyntheticSectionBuilder::default().start(0.).end(30.).bpm(120.).build().unwrap()];
+	let signal = littempo::synthesize_tempo_map(&sections, &SynthesisSettings::default());
+	let settings = littempo::TempoExtractionSettingsBuilder::default().build().unwrap();
+
+	let observer = Recorder { stages: Mutex::new(Vec::new()), cancel_at: None };
+	assert!(littempo::extract_tempo_observed(signal.audio(), &settings, &observer).is_ok());
+	let stages = observer.stages.lock().unwrap().clone();
+	assert_eq!(stages.first(), Some(&Stage::NoveltyCurve));
+	assert_eq!(stages.last(), Some(&Stage::OffsetEstimation));
+
+	let observer = Recorder { stages: Mutex::new(Vec::new()), cancel_at: Some(Stage::Tempogram) };
+	let result = littempo::extract_tempo_observed(signal.audio(), &settings, &observer);
+	assert_eq!(result.err(), Some(Cancelled));
+	assert!(!observer.stages.lock().unwrap().contains(&Stage::CyclicTempogram));
+}
@@ -0,0 +1,50 @@
+use littempo::{SyntheticSectionBuilder, SynthesisSettings, NoProgress};
+
+#[test]
+fn svg_report() {
+	let sections = vec![SyntheticSectionBuilder::default().start(0.).end(30.).bpm(120.).build().unwrap()];
+	let signal = littempo::synthesize_tempo_map(&sections, &SynthesisSettings::default());
+	let settings = littempo::TempoExtractionSettingsBuilder::default().build().unwrap();
+
+	let (novelty_curve, nc_sr) = littempo::extract_novelty_curve(signal.audio(), &settings, &NoProgress).unwrap();
+	let analysis = littempo::analyze_tempogram(&novelty_curve, nc_sr, &settings, &NoProgress).unwrap();
+	let svg = littempo::render_svg_report(signal.audio(), &novelty_curve, nc_sr, Some(&analysis), signal.sections());
+
+	assert!(svg.starts_with("<svg"));
+	assert!(svg.trim_end().ends_with("</svg>"));
+	assert!(svg.contains("120.00 BPM"));
+	assert!(svg.contains("Cyclic Tempogram"));
+}
+
+#[test]
+fn tempo_curve_aligned_with_cyclic_heatmap() {
+	assert!((littempo::cyclic_bin_position(1., 120.)).abs() < 1e-9);
+	assert!((littempo::cyclic_bin_position(2f64.sqrt(), 120.) - 60.).abs() < 1e-9);
+
+	let sections = vec![SyntheticSectionBuilder::default().start(0.).end(30.).bpm(90.).build().unwrap()];
+	let signal = littempo::synthesize_tempo_map(&sections, &SynthesisSettings::default());
+	let settings = littempo::TempoExtractionSettingsBuilder::default().build().unwrap();
+	let (novelty_curve, nc_sr) = littempo::extract_novelty_curve(signal.audio(), &settings, &NoProgress).unwrap();
+	let analysis = littempo::analyze_tempogram(&novelty_curve, nc_sr, &settings, &NoProgress).unwrap();
+	let svg = littempo::render_svg_report(signal.audio(), &novelty_curve, nc_sr, Some(&analysis), signal.sections());
+
+	let attr = |line: &str, name: &str| -> String {
+		let start = line.find(&format!(" {}=\"", name)).unwrap() + name.len() + 3;
+		line[start..].split('"').next().unwrap().to_string()
+	};
+	let cyclic_panel = &svg[svg.find("Cyclic Tempogram with Tempo Curve").unwrap()..];
+
+	// Brightest heatmap cell holds the maximum of the cyclic tempogram
+	let (cell_y, cell_height) = cyclic_panel.lines()
+		.find(|l| l.starts_with("<rect") && l.contains("rgb(253,231,37)"))
+		.map(|l| (attr(l, "y").parse::<f64>().unwrap(), attr(l, "height").parse::<f64>().unwrap()))
+		.unwrap();
+
+	let curve = cyclic_panel.lines().find(|l| l.starts_with("<polyline") && l.contains("#ff3b30")).unwrap();
+	let mut ys: Vec<f64> = attr(curve, "points").split(' ')
+		.map(|p| p.split(',').nth(1).unwrap().parse().unwrap())
+		.collect();
+	ys.sort_by(|a, b| a.partial_cmp(b).unwrap());
+	let median = ys[ys.len() / 2];
+	assert!(median >= cell_y - cell_height && median <= cell_y + 2. * cell_height, "{} not in cell at {}", median, cell_y);
+}
@@ -0,0 +1,56 @@
+use littempo::{SyntheticSectionBuilder, SynthesisSettings, SynthesisSettingsBuilder, EvaluationSettings};
+use litaudio::AudioStorage;
+
+#[test]
+fn synthesized_beats() {
+	let sections = vec![
+		SyntheticSectionBuilder::default().start(0.).end(10.).bpm(120.).offset(Some(0.5)).build().unwrap(),
+		SyntheticSectionBuilder::default().start(10.).end(16.).bpm(90.).beats_per_bar(3).swing(1. / 3.).build().unwrap(),
+	];
+	let signal = littempo::synthesize_tempo_map(&sections, &SynthesisSettings::default());
+
+	assert_eq!(signal.audio().sample_count(), 16 * 22050);
+	assert_eq!(signal.beats().len(), 19 + 9);
+	assert!((signal.beats()[0] - 0.5).abs() < 0.0001);
+	assert!((signal.beats()[19] - 10.).abs() < 0.0001);
+	assert_eq!(signal.downbeats().len(), 5 + 3);
+	assert!((signal.sections()[1].bpm() - 90.).abs() < 0.0001);
+
+	// Silence right before the first beat and sound right after
+	let samples: Vec<f64> = signal.audio().as_iter().cloned().collect();
+	let energy = |from: f32, to: f32| samples[(from * 22050.) as usize..(to * 22050.) as usize].iter().map(|v| v * v).sum::<f64>();
+	assert!(energy(0.3, 0.5) < 1e-9);
+	assert!(energy(0.5, 0.55) > 0.1);
+}
+
+#[test]
+fn synthesized_tempo_ramp() {
+	let sections = vec![SyntheticSectionBuilder::default().start(0.).end(20.).bpm(100.).end_bpm(Some(140.)).build().unwrap()];
+	let settings = SynthesisSettingsBuilder::default().noise_level(0.05).reverb(Some((0.5, 0.2))).build().unwrap();
+	let signal = littempo::synthesize_tempo_map(&sections, &settings);
+
+	let intervals: Vec<f32> = signal.beats().windows(2).map(|w| w[1] - w[0]).collect();
+	assert!((intervals[0] - 0.6).abs() < 0.01);
+	assert!((intervals.last().unwrap() - 60. / 140.).abs() < 0.01);
+	assert!(intervals.windows(2).all(|w| w[1] < w[0]));
+	assert!((signal.sections()[0].bpm() - 120.).abs() < 0.0001);
+}
+
+#[test]
+fn extract_synthesized_tempo() {
+	let sections = vec![SyntheticSectionBuilder::default().start(0.).end(60.).bpm(120.).offset(Some(0.25)).build().unwrap()];
+	let signal = littempo::synthesize_tempo_map(&sections, &SynthesisSettings::default());
+	let settings = littempo::TempoExtractionSettingsBuilder::default().build().unwrap();
+
+	let result = littempo::extract_tempo(signal.audio(), &settings);
+	let scores = littempo::evaluate_tempo(&result, signal.sections(), &EvaluationSettings::default());
+	assert!((scores.accuracy2() - 1.).abs() < 0.01, "{:?}", result);
+
+	// Ground truth is exact. Offsets may land on any beat and are limited by the stft hop of ~23ms
+	assert!(!result.is_empty());
+	for s in &result {
+		assert!((s.bpm() - 120.).abs() <= 0.1, "{:?}", result);
+		let phase = (s.offset() - 0.25).rem_euclid(0.5);
+		assert!(phase.min(0.5 - phase) < 0.01, "{:?}", result);
+	}
+}
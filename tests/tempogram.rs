@@ -72,4 +72,111 @@ fn cyclic_tempogram() {
 	for (target, result) in target_axis.iter().zip(cyclic_tempogram_axis.iter()) {
 		assert!((target - result).abs() < 0.000001);
 	}
-}
\ No newline at end of file
+}
+
+#[test]
+fn cyclic_tempogram_custom() {
+	let crate_path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+	let tempogram: ContainerRM<c64, Dynamic, Dynamic> = litio::read_binary_file(&crate_path.join("assets/test_audio_tempogram.lit")).unwrap();
+	let bpms = RowVec::regspace_rows(U1, D!(571), 30.);
+
+	// Explicit range equal to the derived one yields the same tempogram
+	let (target, target_axis) = littempo::tempogram_to_cyclic_tempogram(&tempogram, &bpms, D!(120), 60.);
+	let settings = littempo::CyclicTempogramSettingsBuilder::default()
+		.octave_range(Some((-1, 4)))
+		.build().unwrap();
+	let (result, result_axis) = littempo::tempogram_to_cyclic_tempogram_custom(&tempogram, &bpms, D!(120), 60., &settings);
+	for (target, result) in target.iter().zip(result.iter()) {
+		assert!((target - result).abs() < 0.000001);
+	}
+	for (target, result) in target_axis.iter().zip(result_axis.iter()) {
+		assert!((target - result).abs() < 0.000001);
+	}
+
+	// Linear interpolation with max pooling stays within the tempogram magnitude bounds
+	let settings = littempo::CyclicTempogramSettingsBuilder::default()
+		.interpolation(littempo::Interpolation::Linear)
+		.pooling(littempo::OctavePooling::Max)
+		.octave_range(Some(littempo::octave_range_covering(&bpms, 60.)))
+		.build().unwrap();
+	let (result, result_axis) = littempo::tempogram_to_cyclic_tempogram_custom(&tempogram, &bpms, D!(120), 60., &settings);
+	let max_mag = tempogram.norm().maximum();
+	assert!(result.iter().all(|v| *v >= 0. && *v <= max_mag + 0.000001));
+	assert!((result_axis[0] - 1.).abs() < 0.000001);
+}
+
+#[test]
+fn cyclic_tempogram_single_octave() {
+	let crate_path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+	let novelty_curve: RowVec<f64, Dynamic> = litio::read_binary_file(&crate_path.join("assets/test_audio_novelty_curve.lit")).unwrap();
+
+	let nc_sr: f64 = 200.;
+	let bpms = RowVec::regspace_rows(U1, D!(11), 170.);
+	let (tempogram, _) = littempo::novelty_curve_to_tempogram_dft(&novelty_curve, nc_sr, D!(1600), D!(40), &bpms);
+
+	let range = littempo::octave_range_covering(&bpms, 60.);
+	assert_eq!(range, (1, 2));
+
+	let settings = littempo::CyclicTempogramSettingsBuilder::default()
+		.octave_range(Some(range))
+		.build().unwrap();
+	let (result, result_axis) = littempo::tempogram_to_cyclic_tempogram_custom(&tempogram, &bpms, D!(120), 60., &settings);
+	let (target, target_axis) = littempo::tempogram_to_cyclic_tempogram(&tempogram, &bpms, D!(120), 60.);
+	assert_eq!(result_axis.col_count(), 120);
+	assert!((result_axis[0] - 1.).abs() < 0.000001);
+	for (target, result) in target.iter().zip(result.iter()) {
+		assert!((target - result).abs() < 0.000001);
+	}
+	for (target, result) in target_axis.iter().zip(result_axis.iter()) {
+		assert!((target - result).abs() < 0.000001);
+	}
+
+	// Only the bins between 170 and 180 bpm (cyclic 1.42 to 1.5) are filled
+	for (i, bin) in result_axis.iter().enumerate() {
+		let filled = result.slice_rows(i).as_iter().any(|v| *v > 0.);
+		if *bin * 120. < 169.9 || *bin * 120. >= 180. {
+			assert!(!filled);
+		}
+	}
+	assert!(result.iter().any(|v| *v > 0.));
+}
+
+#[test]
+fn cyclic_tempogram_empty_octave_range() {
+	assert!(littempo::CyclicTempogramSettingsBuilder::default().octave_range(Some((1, 1))).build().is_err());
+	assert!(littempo::CyclicTempogramSettingsBuilder::default().octave_range(Some((2, 1))).build().is_err());
+	assert!(littempo::CyclicTempogramSettingsBuilder::default().octave_range(Some((1, 2))).build().is_ok());
+	assert!(littempo::CyclicTempogramSettingsBuilder::default().octave_range(None).build().is_ok());
+}
+
+#[test]
+fn cyclic_bpm_mapping() {
+	let crate_path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+	let axis: RowVec<f64, Dynamic> = litio::read_binary_file(&crate_path.join("assets/test_audio_cyclic_tempogram_axis.lit")).unwrap();
+
+	for cyclic in axis.iter().cloned() {
+		assert!((littempo::bpm_to_cyclic(cyclic * 60., 60.).unwrap() - cyclic).abs() < 0.000001);
+		assert!((littempo::bpm_to_cyclic(cyclic * 240., 60.).unwrap() - cyclic).abs() < 0.000001);
+
+		let candidates = littempo::cyclic_to_bpm_candidates(cyclic, 60., (30., 600.));
+		assert!(candidates.iter().any(|bpm| (bpm - cyclic * 60.).abs() < 0.000001));
+		assert!(candidates.len() >= 3);
+		for bpm in candidates {
+			assert!(bpm >= 30. && bpm <= 600.);
+			assert!((littempo::bpm_to_cyclic(bpm, 60.).unwrap() - cyclic).abs() < 0.000001);
+		}
+	}
+
+	// Octaves of ref_tempo up to rounding errors stay at the start of the range
+	for bpm in &[120., 240., 480.] {
+		assert_eq!(littempo::bpm_to_cyclic(*bpm, 60.), Some(1.));
+	}
+	let below = 60. * 2f64.powf(3. - 1e-14);
+	assert_eq!(littempo::bpm_to_cyclic(below, 60.), Some(1.));
+	assert_eq!(littempo::bpm_to_cyclic(0., 60.), None);
+	assert_eq!(littempo::bpm_to_cyclic(-120., 60.), None);
+	assert_eq!(littempo::bpm_to_cyclic(120., 0.), None);
+
+	assert_eq!(littempo::cyclic_to_bpm_candidates(1.5, 60., (80., 100.)), vec![90.]);
+	assert!(littempo::cyclic_to_bpm_candidates(1.5, 60., (100., 170.)).is_empty());
+}
@@ -0,0 +1,17 @@
+use littempo::{SyntheticSectionBuilder, SynthesisSettings, NoProgress};
+
+#[test]
+fn terminal_plot_layout() {
+	let sections = vec![SyntheticSectionBuilder::default().start(0.).end(30.).bpm(120.).build().unwrap()];
+	let signal = littempo::synthesize_tempo_map(&sections, &SynthesisSettings::default());
+	let settings = littempo::TempoExtractionSettingsBuilder::default().build().unwrap();
+
+	let extraction = littempo::extract_tempo_detailed(signal.audio(), &settings, &NoProgress).unwrap();
+	let plot = littempo::render_terminal_plot(extraction.novelty_curve(), *extraction.nc_sr(), extraction.analysis().as_ref(), signal.sections(), 100, 12);
+
+	let lines: Vec<&str> = plot.lines().collect();
+	assert_eq!(lines.len(), 17);
+	assert!(lines.iter().all(|l| l.chars().count() == 100));
+	assert!(lines[0].contains("120.0"));
+	assert!(lines[16].contains("0s"));
+}